@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use island_tracker::io::read_network;
+use island_tracker::Graph;
+
+#[path = "../src/io/by_line.rs"]
+mod by_line;
+
+use by_line::read_network_by_line;
+
+/// Writes a network of `size` by `size` junctions on a lattice, joined by channels with paths of ten points, and
+/// returns its path
+fn lattice(size: i32) -> PathBuf {
+    let mut graph = Graph::new();
+    let index = |x: i32, y: i32| (y * size + x) as usize;
+    for y in 0..size {
+        for x in 0..size {
+            graph.add_vertex((100 * x, 100 * y));
+        }
+    }
+    let add_path = |graph: &mut Graph, from: (i32, i32), to: (i32, i32), weight: f64| {
+        let mut previous = index(from.0, from.1);
+        for step in 1..10 {
+            let point = (
+                100 * from.0 + step * 10 * (to.0 - from.0),
+                100 * from.1 + step * 10 * (to.1 - from.1) + step % 2,
+            );
+            graph.add_vertex(point);
+            graph.add_weighted_edge(previous, graph.vertices() - 1, weight);
+            previous = graph.vertices() - 1;
+        }
+        graph.add_weighted_edge(previous, index(to.0, to.1), weight);
+    };
+    for y in 0..size {
+        for x in 0..size {
+            let weight = ((x * 31 + y * 17) % 1000) as f64 + 0.5;
+            if x + 1 < size {
+                add_path(&mut graph, (x, y), (x + 1, y), weight);
+            }
+            if y + 1 < size {
+                add_path(&mut graph, (x, y), (x, y + 1), weight);
+            }
+        }
+    }
+    let path = std::env::temp_dir().join(format!("island-tracker-bench-{}.txt", size));
+    island_tracker::write_network(&graph, &path).unwrap();
+    path
+}
+
+fn parsers(c: &mut Criterion) {
+    let path = lattice(150);
+    assert_eq!(
+        read_network(0., &path).unwrap(),
+        read_network_by_line(0., &path).unwrap()
+    );
+
+    let mut group = c.benchmark_group("read_network");
+    group.sample_size(20);
+    group.bench_function("bytes", |b| b.iter(|| read_network(100., &path).unwrap()));
+    group.bench_function("lines", |b| b.iter(|| read_network_by_line(100., &path).unwrap()));
+    // all the edges are removed, so only the parsing is measured
+    group.bench_function("bytes without edges", |b| {
+        b.iter(|| read_network(f64::INFINITY, &path).unwrap())
+    });
+    group.bench_function("lines without edges", |b| {
+        b.iter(|| read_network_by_line(f64::INFINITY, &path).unwrap())
+    });
+    group.finish();
+    std::fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, parsers);
+criterion_main!(benches);
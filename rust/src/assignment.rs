@@ -0,0 +1,277 @@
+use crate::geometry::Polygon;
+use crate::graph::UnionFind;
+
+/// Cost of assignments that are not allowed, large enough to never be chosen but small enough to keep sums finite
+const FORBIDDEN: f64 = 1e12;
+
+/// A pair of islands from two layers that may be the same island
+struct Candidate {
+    from: usize,
+    to: usize,
+    /// Cost of a one-to-one assignment
+    cost: f64,
+    /// Cost of attaching one island to the other as part of a merge or split
+    partial_cost: f64,
+}
+
+/// Assigns the islands `from` of one layer to the islands `to` of another layer, given the points (`from_centroids`,
+/// `to_centroids`) used for tracking. Returns the matched pairs of indices sorted by `from`.
+///
+/// The islands are first assigned one-to-one by minimizing the total cost over all islands, where the cost of a pair
+/// is the distance between the centroids relative to the size of the larger island, plus one minus the intersection
+/// over union, plus the absolute log of the ratio of the areas. Pairs costing `max_cost` or more are never assigned.
+/// Islands left unassigned are then attached to the island of the other layer they overlap most, which results in
+/// merges and splits, if the distance plus one minus the fraction of the smaller island covered plus `split_penalty` is
+/// below `max_cost`.
+pub fn assign(
+    from: &[Polygon],
+    from_centroids: &[(f64, f64)],
+    to: &[Polygon],
+    to_centroids: &[(f64, f64)],
+    max_cost: f64,
+    split_penalty: f64,
+) -> Vec<(usize, usize)> {
+    let candidates = candidates(from, from_centroids, to, to_centroids, max_cost, split_penalty);
+
+    // the assignment problem decomposes into the connected components of the candidate pairs
+    let mut components = UnionFind::new(from.len() + to.len());
+    for candidate in &candidates {
+        components.union(candidate.from, from.len() + candidate.to);
+    }
+    let mut groups: Vec<Vec<&Candidate>> = Vec::new();
+    let mut group_of = vec![usize::MAX; from.len() + to.len()];
+    for candidate in &candidates {
+        let root = components.find(candidate.from);
+        if group_of[root] == usize::MAX {
+            group_of[root] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[group_of[root]].push(candidate);
+    }
+
+    let mut pairs = Vec::new();
+    for group in groups {
+        pairs.extend(assign_component(&group, max_cost));
+    }
+
+    // attach the remaining islands to their best partner, creating merges and splits
+    let mut from_assigned = vec![false; from.len()];
+    let mut to_assigned = vec![false; to.len()];
+    for (i, j) in &pairs {
+        from_assigned[*i] = true;
+        to_assigned[*j] = true;
+    }
+    let mut best_from: Vec<Option<&Candidate>> = vec![None; from.len()];
+    let mut best_to: Vec<Option<&Candidate>> = vec![None; to.len()];
+    for candidate in candidates.iter().filter(|candidate| candidate.partial_cost < max_cost) {
+        if !from_assigned[candidate.from]
+            && best_from[candidate.from].is_none_or(|best| candidate.partial_cost < best.partial_cost)
+        {
+            best_from[candidate.from] = Some(candidate);
+        }
+        if !to_assigned[candidate.to]
+            && best_to[candidate.to].is_none_or(|best| candidate.partial_cost < best.partial_cost)
+        {
+            best_to[candidate.to] = Some(candidate);
+        }
+    }
+    for candidate in best_from.into_iter().chain(best_to).flatten() {
+        pairs.push((candidate.from, candidate.to));
+    }
+
+    pairs.sort_unstable();
+    pairs.dedup();
+    pairs
+}
+
+/// Returns all pairs of islands whose centroids are close enough to ever be assigned
+fn candidates(
+    from: &[Polygon],
+    from_centroids: &[(f64, f64)],
+    to: &[Polygon],
+    to_centroids: &[(f64, f64)],
+    max_cost: f64,
+    split_penalty: f64,
+) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for (i, a) in from.iter().enumerate() {
+        let a_area = a.area();
+        for (j, b) in to.iter().enumerate() {
+            let b_area = b.area();
+            let (dx, dy) = (
+                to_centroids[j].0 - from_centroids[i].0,
+                to_centroids[j].1 - from_centroids[i].1,
+            );
+            let distance = dx.hypot(dy) / a_area.max(b_area).sqrt();
+            if distance.is_nan() || distance >= max_cost {
+                continue;
+            }
+
+            let overlap = if a.bounding_boxes_intersect(b) {
+                a.overlap_area(b)
+            } else {
+                0.
+            };
+            let union = a_area + b_area - overlap;
+            let iou = if union > 0. { overlap / union } else { 0. };
+            let ratio = (a_area / b_area).ln().abs();
+            let cost = distance + (1. - iou) + if ratio.is_finite() { ratio } else { FORBIDDEN };
+            let partial_cost = if overlap > 0. {
+                distance + (1. - overlap / a_area.min(b_area)) + split_penalty
+            } else {
+                FORBIDDEN
+            };
+
+            if cost < max_cost || partial_cost < max_cost {
+                candidates.push(Candidate {
+                    from: i,
+                    to: j,
+                    cost,
+                    partial_cost,
+                });
+            }
+        }
+    }
+    candidates
+}
+
+/// Solves the one-to-one assignment of a connected component of candidate pairs. Every island may also stay unassigned
+/// for half of `max_cost`, so a pair is only assigned if it costs less than `max_cost`.
+fn assign_component(candidates: &[&Candidate], max_cost: f64) -> Vec<(usize, usize)> {
+    let mut rows: Vec<usize> = candidates.iter().map(|candidate| candidate.from).collect();
+    let mut columns: Vec<usize> = candidates.iter().map(|candidate| candidate.to).collect();
+    rows.sort_unstable();
+    rows.dedup();
+    columns.sort_unstable();
+    columns.dedup();
+
+    // rows: the islands of `from` followed by a dummy per island of `to`, columns the other way around
+    let size = rows.len() + columns.len();
+    let mut costs = vec![vec![FORBIDDEN; size]; size];
+    for candidate in candidates.iter().filter(|candidate| candidate.cost < max_cost) {
+        let row = rows.binary_search(&candidate.from).unwrap();
+        let column = columns.binary_search(&candidate.to).unwrap();
+        costs[row][column] = candidate.cost;
+    }
+    for i in 0..rows.len() {
+        costs[i][columns.len() + i] = max_cost / 2.;
+    }
+    for j in 0..columns.len() {
+        costs[rows.len() + j][j] = max_cost / 2.;
+        for cost in &mut costs[rows.len() + j][columns.len()..] {
+            *cost = 0.;
+        }
+    }
+
+    hungarian(&costs)
+        .into_iter()
+        .enumerate()
+        .filter(|(row, column)| *row < rows.len() && *column < columns.len() && costs[*row][*column] < max_cost)
+        .map(|(row, column)| (rows[row], columns[column]))
+        .collect()
+}
+
+/// Solves the assignment problem for the square matrix `costs` with the Hungarian method in O(n^3) time.
+/// Returns the column assigned to every row.
+fn hungarian(costs: &[Vec<f64>]) -> Vec<usize> {
+    let n = costs.len();
+    // potentials and the row matched to every column, with a sentinel column 0 and rows numbered from 1
+    let mut u = vec![0.; n + 1];
+    let mut v = vec![0.; n + 1];
+    let mut matched = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+
+    for row in 1..=n {
+        matched[0] = row;
+        let mut column = 0;
+        let mut min_reduced = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[column] = true;
+            let current = matched[column];
+            let mut delta = f64::INFINITY;
+            let mut next = 0;
+            for j in 1..=n {
+                if !used[j] {
+                    let reduced = costs[current - 1][j - 1] - u[current] - v[j];
+                    if reduced < min_reduced[j] {
+                        min_reduced[j] = reduced;
+                        way[j] = column;
+                    }
+                    if min_reduced[j] < delta {
+                        delta = min_reduced[j];
+                        next = j;
+                    }
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[matched[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_reduced[j] -= delta;
+                }
+            }
+            column = next;
+            if matched[column] == 0 {
+                break;
+            }
+        }
+
+        // augment along the alternating path
+        while column != 0 {
+            let previous = way[column];
+            matched[column] = matched[previous];
+            column = previous;
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for j in 1..=n {
+        assignment[matched[j] - 1] = j - 1;
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64, size: f64) -> Polygon {
+        Polygon::new(vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)])
+    }
+
+    fn centroids(islands: &[Polygon]) -> Vec<(f64, f64)> {
+        islands.iter().map(|island| island.centroid().unwrap()).collect()
+    }
+
+    fn assign_islands(from: &[Polygon], to: &[Polygon]) -> Vec<(usize, usize)> {
+        assign(from, &centroids(from), to, &centroids(to), 1.5, 0.25)
+    }
+
+    #[test]
+    fn hungarian_minimizes_total_cost() {
+        let costs = vec![vec![4., 1., 3.], vec![2., 0., 5.], vec![3., 2., 2.]];
+        assert_eq!(hungarian(&costs), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn drifting_islands() {
+        // both islands moved right, the second is too far for its centroid to be contained in either island
+        let from = vec![square(0., 0., 2.), square(3., 0., 2.)];
+        let to = vec![square(1.2, 0., 2.), square(4.2, 0., 2.)];
+        assert_eq!(assign_islands(&from, &to), vec![(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn split_and_far_island() {
+        let from = vec![square(0., 0., 4.), square(20., 0., 1.)];
+        let to = vec![square(0., 0., 4.), square(0., 4., 1.), square(50., 0., 1.)];
+        assert_eq!(assign_islands(&from, &to), vec![(0, 0)]);
+
+        let to = vec![square(0., 0., 2.), square(2., 0., 2.)];
+        let from = vec![square(0., 0., 4.)];
+        assert_eq!(assign_islands(&from, &to), vec![(0, 0), (0, 1)]);
+        assert_eq!(assign_islands(&to, &from), vec![(0, 0), (1, 0)]);
+    }
+}
@@ -0,0 +1,129 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use structopt::clap::ArgMatches;
+use toml::Value;
+
+/// Top-level options that may be given in a configuration file besides the tables of the commands
+const GLOBAL: &[&str] = &["seed", "manifest", "verbose", "quiet"];
+
+/// Top-level keys written to the run manifest that describe the run rather than configure it
+const RECORDED: &[&str] = &["version", "command", "compared_layers", "inputs"];
+
+/// Options of the commands that are given by position rather than by name
+const POSITIONAL: &[&str] = &["network"];
+
+/// Adds the options of the configuration file at `path` to the command line `args`, whose parsed form is `matches`.
+/// Options given on the command line take precedence over the file.
+///
+/// The file holds the global options at the top level and the options of each command in a table named after the
+/// command, with the names of the command line options as keys, e.g.
+///
+/// ```toml
+/// seed = 42
+///
+/// [track]
+/// input_dir = "networks/"
+/// x = 120.0
+/// y = 80.0
+/// ```
+///
+/// Only the table of the command that is run is used. A run manifest is also a valid configuration file.
+pub fn merge(path: &Path, mut args: Vec<OsString>, matches: &ArgMatches) -> Result<Vec<OsString>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("could not read the config {:?}: {}", path, error))?;
+    let config = parse(&text).map_err(|error| format!("invalid config {:?}: {}", path, error))?;
+
+    let (command, command_matches) = matches.subcommand();
+    let given = |key: &str| {
+        matches.occurrences_of(key) > 0 || command_matches.is_some_and(|matches| matches.occurrences_of(key) > 0)
+    };
+
+    let mut options = Vec::new();
+    for (key, value) in &config {
+        if RECORDED.contains(&key.as_str()) || value.is_table() {
+            continue;
+        }
+        if !GLOBAL.contains(&key.as_str()) {
+            return Err(format!(
+                "unknown option {} at the top of the config {:?}, the options of a command belong in its table, e.g. [{}]",
+                key, path, command
+            ));
+        }
+        options.push((key, value));
+    }
+    if let Some(table) = config.get(command) {
+        let table = table
+            .as_table()
+            .ok_or_else(|| format!("{} in the config {:?} is not a table", command, path))?;
+        options.extend(table.iter());
+    }
+
+    for (key, value) in options {
+        let name = key.replace('_', "-");
+        if given(&name) {
+            continue;
+        }
+        let value = argument(value).map_err(|error| format!("option {} in the config {:?}: {}", key, path, error))?;
+        match value {
+            None => {}
+            Some(value) if POSITIONAL.contains(&name.as_str()) => args.push(value.into()),
+            Some(value) if value.is_empty() => args.push(format!("--{}", name).into()),
+            Some(value) => args.push(format!("--{}={}", name, value).into()),
+        }
+    }
+    Ok(args)
+}
+
+fn parse(text: &str) -> Result<toml::value::Table, String> {
+    match text.parse::<Value>() {
+        Ok(Value::Table(table)) => Ok(table),
+        Ok(_) => Err("expected a table".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Returns the command line value of an option, an empty string for a flag that is set and `None` for a flag that
+/// is not set
+fn argument(value: &Value) -> Result<Option<String>, String> {
+    Ok(Some(match value {
+        Value::String(value) => value.clone(),
+        Value::Integer(value) => value.to_string(),
+        Value::Float(value) => value.to_string(),
+        Value::Boolean(true) => String::new(),
+        Value::Boolean(false) => return Ok(None),
+        Value::Array(values) if values.is_empty() => return Ok(None),
+        Value::Array(values) => {
+            let values: Result<Vec<_>, _> = values
+                .iter()
+                .map(|value| match value {
+                    Value::Array(_) | Value::Table(_) | Value::Boolean(_) => {
+                        Err("expected a list of values".to_string())
+                    }
+                    value => argument(value).map(Option::unwrap_or_default),
+                })
+                .collect();
+            values?.join(",")
+        }
+        Value::Datetime(_) | Value::Table(_) => return Err("expected a number, string, boolean or list".to_string()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let config =
+            parse("a = 1e2\nb = [-1, 2.5]\nf = []\nc = \"disk\"\nd = false\ne = true\n[track]\nx = 3").unwrap();
+        assert_eq!(argument(&config["a"]), Ok(Some("100".to_string())));
+        assert_eq!(argument(&config["b"]), Ok(Some("-1,2.5".to_string())));
+        assert_eq!(argument(&config["c"]), Ok(Some("disk".to_string())));
+        assert_eq!(argument(&config["d"]), Ok(None));
+        assert_eq!(argument(&config["f"]), Ok(None));
+        assert_eq!(argument(&config["e"]), Ok(Some(String::new())));
+        assert!(argument(&config["track"]).is_err());
+        assert!(parse("a = ").is_err());
+    }
+}
@@ -0,0 +1,51 @@
+/// Formats a CSV field, unknown values (`NAN`) are left empty
+pub(crate) fn field(value: f64) -> String {
+    if value.is_nan() {
+        String::new()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Quotes a CSV field if it contains a comma or a quote
+pub(crate) fn quote(field: &str) -> String {
+    if field.contains(',') || field.contains('"') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Splits a CSV line into its fields, removing the quotes around quoted fields
+pub(crate) fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields() {
+        let line = [quote("a, b"), quote("say \"hi\""), field(1.5), field(f64::NAN)].join(",");
+        assert_eq!(line, "\"a, b\",\"say \"\"hi\"\"\",1.5,");
+        assert_eq!(split_csv(&line), ["a, b", "say \"hi\"", "1.5", ""]);
+        assert_eq!(split_csv(" x , 2 "), ["x", "2"]);
+    }
+}
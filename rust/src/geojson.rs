@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+
+use serde_json::{json, Value};
+
+use crate::csv::quote;
+use crate::geometry::Polygon;
+use crate::tracking::Tracked;
+
+/// An island of a layer to export as a GeoJSON feature
+pub struct IslandFeature<'a> {
+    pub island: &'a Polygon,
+    pub layer: usize,
+    /// Index of the island among the islands of its layer
+    pub index: usize,
+    /// Weight below which channels are removed from the networks
+    pub delta: f64,
+    /// Water level above which the cells of grids are land
+    pub level: f64,
+    /// Index of the tracked island this island belongs to, `None` if it was not tracked
+    pub track: Option<usize>,
+    /// Names of the seeds of the tracked island
+    pub seed: Option<&'a str>,
+}
+
+impl IslandFeature<'_> {
+    /// Returns the feature with the properties `layer`, `island`, `area`, `delta`, `level`, and `track` and `seed`
+    /// if known. If `time` is given, it is added as the `time` property, so the features of all layers can be
+    /// animated, e.g. by the temporal controller of QGIS.
+    pub fn to_geojson(&self, time: Option<&str>) -> Value {
+        let mut properties = json!({
+            "layer": self.layer,
+            "island": self.index,
+            "area": self.island.area(),
+            "delta": self.delta,
+            "level": self.level,
+        });
+        if let Some(track) = self.track {
+            properties["track"] = json!(track);
+        }
+        if let Some(seed) = self.seed {
+            properties["seed"] = json!(seed);
+        }
+        if let Some(time) = time {
+            properties["time"] = json!(time);
+        }
+        json!({"type": "Feature", "properties": properties, "geometry": self.island.to_geojson()})
+    }
+}
+
+/// Writes the `features` as a GeoJSON `FeatureCollection`, with one feature per line
+pub fn write_feature_collection<W: Write>(features: &[Value], out: &mut W) -> Result<()> {
+    writeln!(out, "{{\"type\": \"FeatureCollection\", \"features\": [")?;
+    for (i, feature) in features.iter().enumerate() {
+        let separator = if i + 1 < features.len() { "," } else { "" };
+        writeln!(out, "{}{}", feature, separator)?;
+    }
+    writeln!(out, "]}}")?;
+    Ok(())
+}
+
+/// Returns the features of the islands of the `tracked` islands, numbered in the given order and sorted by layer.
+/// `seeds` holds the names of the seeds of every tracked island and `layers` the islands of every layer they reach.
+pub fn tracked_features<'a>(
+    tracked: &[&Tracked],
+    seeds: &'a [String],
+    layers: &'a BTreeMap<usize, Vec<Polygon>>,
+    delta: f64,
+    level: f64,
+) -> Vec<IslandFeature<'a>> {
+    let mut features = Vec::new();
+    for (track, tracked) in tracked.iter().enumerate() {
+        for &(layer, index) in &tracked.islands {
+            features.push(IslandFeature {
+                island: &layers[&layer][index],
+                layer,
+                index,
+                delta,
+                level,
+                track: Some(track),
+                seed: Some(&seeds[track]).filter(|name| !name.is_empty()).map(String::as_str),
+            });
+        }
+    }
+    features.sort_by_key(|feature| (feature.layer, feature.index));
+    features
+}
+
+/// The times of the layers, layer `i` is `origin + interval * i` seconds after the Unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clock {
+    pub origin: i64,
+    pub interval: i64,
+}
+
+impl Clock {
+    /// Returns the time of `layer`, see [`timestamp`]
+    pub fn time(&self, layer: usize) -> String {
+        timestamp(self.origin + self.interval * layer as i64)
+    }
+}
+
+/// How the geometry of the islands is written in CSV files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Wkt,
+    /// Hexadecimal WKB
+    Wkb,
+}
+
+/// Writes the `features` as a GeoJSON `FeatureCollection`. If a `clock` is given, the time of the layer is added to
+/// every feature.
+pub fn write_geojson<W: Write>(features: &[IslandFeature], clock: Option<&Clock>, out: &mut W) -> Result<()> {
+    let features: Vec<_> = features
+        .iter()
+        .map(|feature| feature.to_geojson(clock.map(|clock| clock.time(feature.layer)).as_deref()))
+        .collect();
+    write_feature_collection(&features, out)
+}
+
+/// Writes the `features` as CSV with the geometry in the last column. If a `clock` is given, the time of the layer is
+/// added to every feature.
+pub fn write_csv<W: Write>(
+    features: &[IslandFeature],
+    encoding: Encoding,
+    clock: Option<&Clock>,
+    out: &mut W,
+) -> Result<()> {
+    let geometry = match encoding {
+        Encoding::Wkt => "wkt",
+        Encoding::Wkb => "wkb",
+    };
+    let time_column = if clock.is_some() { ",time" } else { "" };
+    writeln!(
+        out,
+        "layer,island,area,delta,level,track,seed{},{}",
+        time_column, geometry
+    )?;
+    for feature in features {
+        let geometry = match encoding {
+            Encoding::Wkt => format!("\"{}\"", feature.island.to_wkt()),
+            Encoding::Wkb => feature
+                .island
+                .to_wkb()
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect(),
+        };
+        let time = clock
+            .map(|clock| format!(",{}", clock.time(feature.layer)))
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{}{},{}",
+            feature.layer,
+            feature.index,
+            feature.island.area(),
+            feature.delta,
+            feature.level,
+            feature.track.map(|track| track.to_string()).unwrap_or_default(),
+            feature.seed.map(quote).unwrap_or_default(),
+            time,
+            geometry
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the UTC date and time `seconds` after the Unix epoch in ISO 8601 format, e.g. `2021-06-01T12:00:00Z`
+pub fn timestamp(seconds: i64) -> String {
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    // the civil date of a day count, shifted to eras of 400 years starting on the 1st of March
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp(951782400 + 3723), "2000-02-29T01:02:03Z");
+        assert_eq!(timestamp(1622548800), "2021-06-01T12:00:00Z");
+        assert_eq!(timestamp(-86400), "1969-12-31T00:00:00Z");
+    }
+
+    #[test]
+    fn feature_collection() {
+        let island = Polygon::new(vec![(0., 0.), (2., 0.), (2., 2.), (0., 2.)]);
+        let feature = IslandFeature {
+            island: &island,
+            layer: 3,
+            index: 1,
+            delta: 100.,
+            level: 0.,
+            track: Some(0),
+            seed: None,
+        };
+        let feature = feature.to_geojson(Some("1970-01-04T00:00:00Z"));
+        assert_eq!(feature["properties"]["area"], json!(4.));
+        assert_eq!(feature["properties"]["track"], json!(0));
+        assert!(feature["properties"].get("seed").is_none());
+
+        let mut out = Vec::new();
+        write_feature_collection(&[feature.clone(), feature], &mut out).unwrap();
+        let collection: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(collection["features"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            collection["features"][1]["properties"]["time"],
+            json!("1970-01-04T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn csv_features() {
+        let island = Polygon::new(vec![(0., 0.), (2., 0.), (2., 2.), (0., 2.)]);
+        let feature = IslandFeature {
+            island: &island,
+            layer: 2,
+            index: 0,
+            delta: 100.,
+            level: 0.,
+            track: Some(1),
+            seed: Some("north, east"),
+        };
+        let clock = Clock {
+            origin: 0,
+            interval: 86400,
+        };
+        let mut out = Vec::new();
+        write_csv(&[feature], Encoding::Wkt, Some(&clock), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "layer,island,area,delta,level,track,seed,time,wkt\n\
+             2,0,4,100,0,1,\"north, east\",1970-01-03T00:00:00Z,\"POLYGON ((0 0, 2 0, 2 2, 0 2, 0 0))\"\n"
+        );
+    }
+}
@@ -1,8 +1,8 @@
-use std::fmt;
 use crate::geometry::dcel::point;
+use std::fmt;
 
-use point::Point;
 use crate::geometry::dcel::util::get_breakpoint_x;
+use point::Point;
 
 const NIL: usize = !0;
 type TripleSite = (Point, Point, Point);
@@ -34,18 +34,32 @@ pub struct BeachNode {
 
 impl fmt::Debug for BeachNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "p: {:?}, l: {:?}, r: {:?}, item: {:?}", self.parent, self.left_child, self.right_child, self.item)
+        write!(
+            f,
+            "p: {:?}, l: {:?}, r: {:?}, item: {:?}",
+            self.parent, self.left_child, self.right_child, self.item
+        )
     }
 }
 
 impl BeachNode {
     fn make_root(item: BeachItem) -> Self {
-        BeachNode { parent: None, left_child: None, right_child: None, item: item}
+        BeachNode {
+            parent: None,
+            left_child: None,
+            right_child: None,
+            item: item,
+        }
     }
 
     pub fn make_arc(parent: Option<usize>, item: BeachItem) -> Self {
         if let BeachItem::Leaf(_) = item {
-            BeachNode { parent: parent, left_child: None, right_child: None, item: item}
+            BeachNode {
+                parent: parent,
+                left_child: None,
+                right_child: None,
+                item: item,
+            }
         } else {
             panic!("make_arc can only make Leaf items!");
         }
@@ -79,7 +93,10 @@ impl fmt::Debug for Arc {
 
 impl Arc {
     pub fn new(site: Point, site_event: Option<usize>) -> Self {
-        Arc { site: site, site_event: site_event }
+        Arc {
+            site: site,
+            site_event: site_event,
+        }
     }
 }
 
@@ -91,19 +108,30 @@ pub struct BreakPoint {
 
 impl fmt::Debug for BreakPoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "left: {:?}, right: {:?}, halfedge: {}", self.left_site, self.right_site, self.halfedge)
+        write!(
+            f,
+            "left: {:?}, right: {:?}, halfedge: {}",
+            self.left_site, self.right_site, self.halfedge
+        )
     }
 }
 
 impl BreakPoint {
     pub fn new(left_site: Point, right_site: Point, halfedge: usize) -> Self {
-        BreakPoint { left_site: left_site, right_site: right_site, halfedge: halfedge }
+        BreakPoint {
+            left_site: left_site,
+            right_site: right_site,
+            halfedge: halfedge,
+        }
     }
 }
 
 impl BeachLine {
     pub fn new() -> Self {
-        BeachLine { nodes: vec![], root: NIL }
+        BeachLine {
+            nodes: vec![],
+            root: NIL,
+        }
     }
 
     pub fn is_empty(&self) -> bool {
@@ -111,7 +139,10 @@ impl BeachLine {
     }
 
     pub fn insert_point(&mut self, pt: Point) {
-        let this_arc = Arc {site: pt, site_event: None};
+        let this_arc = Arc {
+            site: pt,
+            site_event: None,
+        };
         let this_item = BeachItem::Leaf(this_arc);
         let this_node = BeachNode::make_root(this_item);
         self.nodes.push(this_node);
@@ -119,15 +150,22 @@ impl BeachLine {
     }
 
     pub fn get_arc_above(&self, pt: Point) -> usize {
-        if self.is_empty() { panic!("can't get_arc_above on empty beachline!"); }
+        if self.is_empty() {
+            panic!("can't get_arc_above on empty beachline!");
+        }
         let mut current_node = self.root;
         loop {
             match self.nodes[current_node].item {
-                BeachItem::Leaf(_) => { return current_node; }
+                BeachItem::Leaf(_) => {
+                    return current_node;
+                }
                 BeachItem::Internal(ref breakpoint) => {
                     let x_bp = get_breakpoint_x(breakpoint, pt.y());
-                    if pt.x() < x_bp { current_node = self.nodes[current_node].left_child.unwrap(); }
-                    else { current_node = self.nodes[current_node].right_child.unwrap(); }
+                    if pt.x() < x_bp {
+                        current_node = self.nodes[current_node].left_child.unwrap();
+                    } else {
+                        current_node = self.nodes[current_node].right_child.unwrap();
+                    }
                 }
             }
         }
@@ -176,11 +214,13 @@ impl BeachLine {
     }
 
     pub fn get_left_arc(&self, node: Option<usize>) -> Option<usize> {
-        node.and_then(|node| self.predecessor(node)).and_then(|left| self.predecessor(left))
+        node.and_then(|node| self.predecessor(node))
+            .and_then(|left| self.predecessor(left))
     }
 
     pub fn get_right_arc(&self, node: Option<usize>) -> Option<usize> {
-        node.and_then(|node| self.successor(node)).and_then(|right| self.successor(right))
+        node.and_then(|node| self.successor(node))
+            .and_then(|right| self.successor(right))
     }
 
     pub fn get_leftward_triple(&self, node: usize) -> Option<TripleSite> {
@@ -193,7 +233,9 @@ impl BeachLine {
 
         if this_site.is_some() && left_site.is_some() && left_left_site.is_some() {
             Some((left_left_site.unwrap(), left_site.unwrap(), this_site.unwrap()))
-        } else { None }
+        } else {
+            None
+        }
     }
 
     pub fn get_rightward_triple(&self, node: usize) -> Option<TripleSite> {
@@ -206,7 +248,9 @@ impl BeachLine {
 
         if this_site.is_some() && right_site.is_some() && right_right_site.is_some() {
             Some((this_site.unwrap(), right_site.unwrap(), right_right_site.unwrap()))
-        } else { None }
+        } else {
+            None
+        }
     }
 
     pub fn get_centered_triple(&self, node: usize) -> Option<TripleSite> {
@@ -219,7 +263,9 @@ impl BeachLine {
 
         if this_site.is_some() && right_site.is_some() && left_site.is_some() {
             Some((left_site.unwrap(), this_site.unwrap(), right_site.unwrap()))
-        } else { None }
+        } else {
+            None
+        }
     }
 
     pub fn get_site(&self, node: Option<usize>) -> Option<Point> {
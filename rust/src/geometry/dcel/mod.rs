@@ -8,6 +8,7 @@ mod util;
 use crate::geometry::Polygon;
 use fxhash::FxHashMap;
 use geo::point;
+use log::warn;
 use point::Point;
 use util::{segment_intersection, Segment};
 
@@ -26,6 +27,12 @@ pub struct DCEL {
     adj: Vec<Vec<usize>>,
 }
 
+impl Default for DCEL {
+    fn default() -> Self {
+        DCEL::new()
+    }
+}
+
 impl DCEL {
     /// Construct an empty DCEL
     pub fn new() -> Self {
@@ -121,7 +128,14 @@ impl DCEL {
     }
 
     pub fn add_edge_unchecked(&mut self, a: &(i32, i32), b: &(i32, i32)) {
+        self.add_weighted_edge_unchecked(a, b, f64::NAN);
+    }
+
+    /// Adds an edge between `a` and `b` whose halfedges carry `weight`, without checking for intersections
+    pub fn add_weighted_edge_unchecked(&mut self, a: &(i32, i32), b: &(i32, i32), weight: f64) {
         let twins = self.add_twins();
+        self.halfedges[twins.0].weight = weight;
+        self.halfedges[twins.1].weight = weight;
         if !self.map.contains_key(a) {
             self.map.insert(*a, self.vertices.len());
             let v = Vertex {
@@ -201,7 +215,7 @@ impl DCEL {
 
         for e in &self.halfedges {
             if e.next == NIL {
-                println!("{}", e.origin);
+                warn!("The halfedge from vertex {} has no next halfedge", e.origin);
             }
         }
     }
@@ -226,8 +240,7 @@ impl DCEL {
         let prev_pt = start_pt;
 
         for (int_pt, this_cut_edge) in intersections {
-            let (new_line_needs_next, new_line_needs_prev, new_pt_ind) =
-                add_twins_from_pt(int_pt, self);
+            let (new_line_needs_next, new_line_needs_prev, new_pt_ind) = add_twins_from_pt(int_pt, self);
             self.halfedges[line_needs_prev].origin = new_pt_ind;
 
             let mut cut_edge = this_cut_edge;
@@ -246,6 +259,7 @@ impl DCEL {
                 twin: old_cut_twin,
                 face: NIL,
                 prev: NIL,
+                weight: self.halfedges[cut_edge].weight,
                 alive: true,
             };
             self.halfedges.push(cut_ext_he);
@@ -261,6 +275,7 @@ impl DCEL {
                 twin: cut_edge,
                 face: NIL,
                 prev: NIL,
+                weight: self.halfedges[cut_edge].weight,
                 alive: true,
             };
             self.halfedges.push(twin_ext_he);
@@ -328,27 +343,30 @@ impl DCEL {
                 continue;
             }
             let mut this_poly = vec![];
+            let mut min_weight = f64::NAN;
             let start_edge = face.outer_component;
             let mut current_edge = start_edge;
             loop {
                 this_poly.push(self.get_origin(current_edge));
+                // `min` ignores unknown (NAN) weights
+                min_weight = min_weight.min(self.halfedges[current_edge].weight);
                 current_edge = self.halfedges[current_edge].next;
                 if current_edge == start_edge {
                     break;
                 }
             }
-            result.push(this_poly);
+            result.push((this_poly, min_weight));
         }
 
         // remove the outer face
-        result.sort_by(|a, b| a.len().cmp(&b.len()));
+        result.sort_by_key(|a| a.0.len());
         result.pop();
 
         result
             .into_iter()
-            .map(|list| {
+            .map(|(list, min_weight)| {
                 let points = list.into_iter().map(|p| (p.x(), p.y())).collect();
-                Polygon::new(points)
+                Polygon::new(points).with_boundary_weight(min_weight)
             })
             .collect()
     }
@@ -414,16 +432,14 @@ pub struct HalfEdge {
     pub next: usize, // index of halfedge
     face: usize, // index of face
     prev: usize, // index of halfedge
+    /// Weight of the network edge this halfedge belongs to, `NAN` if unknown
+    weight: f64,
     alive: bool,
 }
 
 impl fmt::Debug for HalfEdge {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "origin: {}, twin: {}, next: {}",
-            self.origin, self.twin, self.next
-        )
+        write!(f, "origin: {}, twin: {}, next: {}", self.origin, self.twin, self.next)
     }
 }
 
@@ -436,6 +452,7 @@ impl HalfEdge {
             next: NIL,
             face: NIL,
             prev: NIL,
+            weight: f64::NAN,
             alive: true,
         }
     }
@@ -1,9 +1,9 @@
-use ordered_float::OrderedFloat;
-use crate::geometry::dcel::point;
 use crate::geometry::dcel::beachline;
+use crate::geometry::dcel::point;
+use ordered_float::OrderedFloat;
 
-use point::Point;
 use beachline::BreakPoint;
+use point::Point;
 
 type TripleSite = (Point, Point, Point);
 
@@ -16,7 +16,9 @@ pub fn segment_intersection(seg1: Segment, seg2: Segment) -> Option<Point> {
     let s = seg2[1] - c;
 
     let denom = r.cross(s);
-    if denom == 0.0 { return None; }
+    if denom == 0.0 {
+        return None;
+    }
 
     let numer_a = (c - a).cross(s);
     let numer_c = (c - a).cross(r);
@@ -24,14 +26,18 @@ pub fn segment_intersection(seg1: Segment, seg2: Segment) -> Option<Point> {
     let t = numer_a / denom;
     let u = numer_c / denom;
 
-    if t < 0.0 || t > 1.0 || u < 0.0 || u > 1.0 { return None; }
+    if t < 0.0 || t > 1.0 || u < 0.0 || u > 1.0 {
+        return None;
+    }
 
     return Some(a + r * t);
 }
 
 pub fn circle_bottom(triple_site: TripleSite) -> Option<OrderedFloat<f64>> {
     let circle_center = circle_center(triple_site);
-    if let None = circle_center { return None; }
+    if let None = circle_center {
+        return None;
+    }
     let circle_center = circle_center.unwrap();
 
     let (_, _, p3) = triple_site;
@@ -64,10 +70,11 @@ pub fn circle_center(triple_site: TripleSite) -> Option<Point> {
     let numer = c1 * a2 - c2 * a1;
     let denom = b1 * a2 - b2 * a1;
 
-    if denom == 0.0 { return None; }
+    if denom == 0.0 {
+        return None;
+    }
     let y_cen = numer / denom;
 
-
     let x_cen = if a2 != 0.0 {
         (c2 - b2 * y_cen) / a2
     } else {
@@ -105,11 +112,7 @@ pub fn get_breakpoint_x(bp: &BreakPoint, yl: f64) -> f64 {
     let numer = ay_s * bx_s - discrim.sqrt();
     let denom = ay_s - by_s;
 
-    let mut x_bp = if denom != 0.0 {
-        numer / denom
-    } else {
-        bx_s / 2.
-    };
+    let mut x_bp = if denom != 0.0 { numer / denom } else { bx_s / 2. };
     x_bp += ax; // shift back to original frame
 
     return x_bp;
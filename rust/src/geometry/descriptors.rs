@@ -0,0 +1,118 @@
+/// Shape descriptors of an island
+#[derive(Debug, Clone, PartialEq)]
+pub struct Descriptors {
+    /// Area enclosed by the boundary
+    pub area: f64,
+    /// Length of the boundary
+    pub perimeter: f64,
+    /// Centroid of the enclosed area
+    pub centroid: (f64, f64),
+    /// Lower left and upper right corner of the axis-aligned bounding box
+    pub bounding_box: ((f64, f64), (f64, f64)),
+    /// Ratio between the area and the area of the convex hull, 1 for convex polygons
+    pub convexity: f64,
+    /// Isoperimetric quotient `4 pi A / P^2`, 1 for a disk
+    pub compactness: f64,
+    /// `1 - width / length` of the minimum-area bounding rectangle, 0 for a square
+    pub elongation: f64,
+    /// Angle in radians in `[0, pi)` of the major axis, i.e., the long side of the minimum-area bounding rectangle
+    pub orientation: f64,
+    /// Center of the smallest enclosing disk
+    pub disk_center: (f64, f64),
+    /// Radius of the smallest enclosing disk
+    pub disk_radius: f64,
+}
+
+/// A rectangle with arbitrary orientation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rectangle {
+    /// Length of the long side
+    pub length: f64,
+    /// Length of the short side
+    pub width: f64,
+    /// Angle in radians in `[0, pi)` of the long side
+    pub orientation: f64,
+}
+
+impl Rectangle {
+    pub fn area(&self) -> f64 {
+        self.length * self.width
+    }
+}
+
+/// Computes the minimum-area bounding rectangle of the convex polygon `hull`, one of its sides is
+/// collinear with an edge of the hull. Returns `None` if `hull` is empty.
+pub fn minimum_area_rectangle(hull: &[(f64, f64)]) -> Option<Rectangle> {
+    if hull.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<Rectangle> = None;
+    for i in 0..hull.len() {
+        let a = hull[i];
+        let b = hull[(i + 1) % hull.len()];
+        let norm = (b.0 - a.0).hypot(b.1 - a.1);
+        if norm == 0. {
+            continue;
+        }
+        let u = ((b.0 - a.0) / norm, (b.1 - a.1) / norm);
+
+        let (mut min_u, mut max_u) = (f64::INFINITY, f64::NEG_INFINITY);
+        let (mut min_v, mut max_v) = (f64::INFINITY, f64::NEG_INFINITY);
+        for p in hull {
+            let along = (p.0 - a.0) * u.0 + (p.1 - a.1) * u.1;
+            let across = (p.1 - a.1) * u.0 - (p.0 - a.0) * u.1;
+            min_u = min_u.min(along);
+            max_u = max_u.max(along);
+            min_v = min_v.min(across);
+            max_v = max_v.max(across);
+        }
+
+        let extent_u = max_u - min_u;
+        let extent_v = max_v - min_v;
+        let (length, width, angle) = if extent_u >= extent_v {
+            (extent_u, extent_v, u.1.atan2(u.0))
+        } else {
+            (extent_v, extent_u, u.1.atan2(u.0) + std::f64::consts::FRAC_PI_2)
+        };
+
+        let rectangle = Rectangle {
+            length,
+            width,
+            orientation: angle.rem_euclid(std::f64::consts::PI),
+        };
+        if best.as_ref().is_none_or(|r| rectangle.area() < r.area()) {
+            best = Some(rectangle);
+        }
+    }
+
+    // all vertices coincide
+    best.or(Some(Rectangle {
+        length: 0.,
+        width: 0.,
+        orientation: 0.,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_rectangle() {
+        let hull = vec![(0., 0.), (2., 2.), (1., 3.), (-1., 1.)];
+        let rectangle = minimum_area_rectangle(&hull).unwrap();
+        assert!((rectangle.area() - 4.).abs() < 1e-9);
+        assert!((rectangle.length - 8f64.sqrt()).abs() < 1e-9);
+        assert!((rectangle.orientation - std::f64::consts::FRAC_PI_4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn vertical_rectangle() {
+        let hull = vec![(0., 0.), (1., 0.), (1., 4.), (0., 4.)];
+        let rectangle = minimum_area_rectangle(&hull).unwrap();
+        assert_eq!(rectangle.length, 4.);
+        assert_eq!(rectangle.width, 1.);
+        assert!((rectangle.orientation - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+    }
+}
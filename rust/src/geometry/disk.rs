@@ -23,4 +23,8 @@ impl Disk {
     pub fn centroid(&self) -> (f64, f64) {
         self.center
     }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
 }
@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+
+use ordered_float::OrderedFloat;
+
+/// A segment between two points with integer coordinates
+pub type Segment = ((i32, i32), (i32, i32));
+
+/// Distance below which points computed in floating point are considered equal
+const EPSILON: f64 = 1e-6;
+
+type Point = (f64, f64);
+
+/// A point where segments intersect away from the end of at least one of them, found by [`intersections`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intersection {
+    pub point: (f64, f64),
+    /// The segments that have the point inside
+    pub inside: Vec<usize>,
+    /// The segments that start or end at the point
+    pub ends: Vec<usize>,
+}
+
+/// Returns for every segment the points where it is intersected by another segment away from its own ends, see
+/// [`intersections`], sorted from its first to its last point
+pub fn interior_intersections(segments: &[Segment]) -> Vec<Vec<(f64, f64)>> {
+    let mut points = vec![Vec::new(); segments.len()];
+    for intersection in intersections(segments) {
+        for &i in &intersection.inside {
+            points[i].push(intersection.point);
+        }
+    }
+    for (i, points) in points.iter_mut().enumerate() {
+        let (ax, ay) = (segments[i].0 .0 as f64, segments[i].0 .1 as f64);
+        let distance = |p: &Point| (p.0 - ax).abs() + (p.1 - ay).abs();
+        points.sort_by(|p, q| distance(p).total_cmp(&distance(q)));
+        points.dedup_by(|p, q| (p.0 - q.0).abs() <= EPSILON && (p.1 - q.1).abs() <= EPSILON);
+    }
+    points
+}
+
+/// Finds all intersections of `segments` with the sweep-line algorithm of Bentley and Ottmann: where two segments
+/// cross, where the end of a segment lies on another one, and where collinear segments start to overlap. Segments
+/// that only share an end do not intersect, and neither do segments of length zero. The intersections are ordered
+/// by x and then y.
+///
+/// The sweep line moves from left to right over the events, the ends of the segments and the intersections found so
+/// far. Every event reorders the segments through it, and only segments that become neighbours on the sweep line are
+/// tested for an intersection. The segments on the sweep line are kept in a vector ordered from bottom to top, in
+/// which the segments through an event are found by binary search, so `n` segments with `k` intersections take
+/// `O((n + k) log n)` comparisons. Inserting into and removing from the vector moves the segments above the event,
+/// a memory move that is cheap compared to the comparisons.
+pub fn intersections(segments: &[Segment]) -> Vec<Intersection> {
+    let mut sweep = Sweep {
+        segments: segments
+            .iter()
+            .map(|&(a, b)| {
+                let (a, b) = (a.min(b), a.max(b));
+                ((a.0 as f64, a.1 as f64), (b.0 as f64, b.1 as f64))
+            })
+            .collect(),
+        ends: segments.iter().map(|&(a, b)| (a.min(b), a.max(b))).collect(),
+        events: BTreeMap::new(),
+        status: Vec::new(),
+        intersections: Vec::new(),
+    };
+    for i in 0..segments.len() {
+        if sweep.ends[i].0 == sweep.ends[i].1 {
+            continue;
+        }
+        let (left, right) = sweep.segments[i];
+        sweep.event(left).push(i);
+        sweep.event(right);
+    }
+    while let Some((&(x, y), _)) = sweep.events.iter().next() {
+        let starting = sweep.events.remove(&(x, y)).unwrap_or_default();
+        sweep.handle((x.0, y.0), starting);
+    }
+    sweep.intersections
+}
+
+struct Sweep {
+    /// The segments from their lower left to their upper right end
+    segments: Vec<(Point, Point)>,
+    /// The exact ends of the segments, in the same order
+    ends: Vec<Segment>,
+    /// The events ordered by x and then y, with the segments starting there
+    events: BTreeMap<(OrderedFloat<f64>, OrderedFloat<f64>), Vec<usize>>,
+    /// The segments intersecting the sweep line, from bottom to top
+    status: Vec<usize>,
+    intersections: Vec<Intersection>,
+}
+
+impl Sweep {
+    fn event(&mut self, (x, y): Point) -> &mut Vec<usize> {
+        self.events.entry((OrderedFloat(x), OrderedFloat(y))).or_default()
+    }
+
+    /// Returns the y-coordinate of segment `i` on the vertical line through `p`, which is `p.1` for a vertical
+    /// segment through `p`
+    fn y_at(&self, i: usize, p: Point) -> f64 {
+        let ((x1, y1), (x2, y2)) = self.segments[i];
+        if x1 == x2 {
+            p.1.max(y1).min(y2)
+        } else {
+            y1 + (p.0 - x1) * (y2 - y1) / (x2 - x1)
+        }
+    }
+
+    /// Returns the slope of segment `i`, infinite for vertical segments
+    fn slope(&self, i: usize) -> f64 {
+        let ((x1, y1), (x2, y2)) = self.segments[i];
+        if x1 == x2 {
+            f64::INFINITY
+        } else {
+            (y2 - y1) / (x2 - x1)
+        }
+    }
+
+    fn handle(&mut self, p: Point, mut starting: Vec<usize>) {
+        // the segments on the sweep line through p, which end at p or continue through it, are consecutive
+        let lower = self.status.partition_point(|&i| self.y_at(i, p) < p.1 - EPSILON);
+        let upper = lower + self.status[lower..].partition_point(|&i| self.y_at(i, p) <= p.1 + EPSILON);
+        let mut ending = Vec::new();
+        let mut through = Vec::new();
+        for i in self.status.drain(lower..upper) {
+            let (_, right) = self.segments[i];
+            if (right.0 - p.0).abs() <= EPSILON && (right.1 - p.1).abs() <= EPSILON {
+                ending.push(i);
+            } else {
+                through.push(i);
+            }
+        }
+        if !through.is_empty() && starting.len() + ending.len() + through.len() > 1 {
+            self.intersections.push(Intersection {
+                point: p,
+                inside: through.clone(),
+                ends: starting.iter().chain(&ending).copied().collect(),
+            });
+        }
+
+        // the segments continuing after p, ordered by their slope as they leave p
+        starting.append(&mut through);
+        starting.sort_by(|&i, &j| self.slope(i).total_cmp(&self.slope(j)));
+        let position = lower;
+        let count = starting.len();
+        self.status.splice(position..position, starting);
+
+        if count == 0 {
+            if position > 0 && position < self.status.len() {
+                self.find_event(self.status[position - 1], self.status[position], p);
+            }
+        } else {
+            if position > 0 {
+                self.find_event(self.status[position - 1], self.status[position], p);
+            }
+            if position + count < self.status.len() {
+                self.find_event(self.status[position + count - 1], self.status[position + count], p);
+            }
+        }
+    }
+
+    /// Adds the event where the segments `i` and `j` cross, if they cross properly after `p`
+    fn find_event(&mut self, i: usize, j: usize, p: Point) {
+        let ((a, b), (c, d)) = (self.ends[i], self.ends[j]);
+        if orientation(a, b, c) * orientation(a, b, d) >= 0 || orientation(c, d, a) * orientation(c, d, b) >= 0 {
+            return;
+        }
+        let ((ax, ay), (bx, by)) = self.segments[i];
+        let ((cx, cy), (dx, dy)) = self.segments[j];
+        let t = ((cx - ax) * (dy - cy) - (cy - ay) * (dx - cx)) / ((bx - ax) * (dy - cy) - (by - ay) * (dx - cx));
+        let q = (ax + t * (bx - ax), ay + t * (by - ay));
+        if q.0 > p.0 + EPSILON || ((q.0 - p.0).abs() <= EPSILON && q.1 > p.1 + EPSILON) {
+            self.event(q);
+        }
+    }
+}
+
+/// Returns the sign of the cross product of `b - a` and `c - a`, computed exactly
+fn orientation(a: (i32, i32), b: (i32, i32), c: (i32, i32)) -> i128 {
+    let cross = (b.0 as i128 - a.0 as i128) * (c.1 as i128 - a.1 as i128)
+        - (b.1 as i128 - a.1 as i128) * (c.0 as i128 - a.0 as i128);
+    cross.signum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crossings() {
+        let segments = [((0, 0), (4, 4)), ((0, 4), (4, 0)), ((0, 2), (4, 2)), ((10, 0), (11, 0))];
+        let points = interior_intersections(&segments);
+        assert_eq!(points, vec![vec![(2., 2.)], vec![(2., 2.)], vec![(2., 2.)], vec![]]);
+    }
+
+    #[test]
+    fn touching_and_overlapping() {
+        let segments = [
+            ((0, 0), (6, 0)),
+            // ends on the first segment
+            ((2, 0), (2, 5)),
+            // overlaps the first segment
+            ((6, 0), (4, 0)),
+            // vertical, crossed by the last segment
+            ((8, 1), (8, -3)),
+            ((9, -1), (7, -1)),
+            // shares an end with the first segment
+            ((0, 0), (-3, 2)),
+        ];
+        let points = interior_intersections(&segments);
+        assert_eq!(points[0], vec![(2., 0.), (4., 0.)]);
+        assert!(points[1].is_empty() && points[2].is_empty() && points[5].is_empty());
+        assert_eq!(points[3], vec![(8., -1.)]);
+        assert_eq!(points[4], vec![(8., -1.)]);
+    }
+
+    #[test]
+    fn random_segments() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut point = || (rng.gen_range(0..20), rng.gen_range(0..20));
+        let segments: Vec<Segment> = (0..60).map(|_| (point(), point())).collect();
+        let points = interior_intersections(&segments);
+
+        // every pair of segments, the crossing or the ends of one segment inside the other one
+        let inside = |(a, b): Segment, p: (i32, i32)| {
+            p != a && p != b && orientation(a, b, p) == 0 && a.min(b) <= p && p <= a.max(b)
+        };
+        for (i, &(a, b)) in segments.iter().enumerate() {
+            let mut expected = Vec::new();
+            for (j, &(c, d)) in segments.iter().enumerate() {
+                if i == j || a == b || c == d {
+                    continue;
+                }
+                if orientation(a, b, c) * orientation(a, b, d) < 0 && orientation(c, d, a) * orientation(c, d, b) < 0 {
+                    let (ax, ay, bx, by) = (a.0 as f64, a.1 as f64, b.0 as f64, b.1 as f64);
+                    let (cx, cy, dx, dy) = (c.0 as f64, c.1 as f64, d.0 as f64, d.1 as f64);
+                    let t = ((cx - ax) * (dy - cy) - (cy - ay) * (dx - cx))
+                        / ((bx - ax) * (dy - cy) - (by - ay) * (dx - cx));
+                    expected.push((ax + t * (bx - ax), ay + t * (by - ay)));
+                }
+                for p in [c, d] {
+                    if inside((a, b), p) {
+                        expected.push((p.0 as f64, p.1 as f64));
+                    }
+                }
+            }
+            let distance = |p: &(f64, f64)| (p.0 - a.0 as f64).abs() + (p.1 - a.1 as f64).abs();
+            expected.sort_by(|p, q| distance(p).total_cmp(&distance(q)));
+            expected.dedup_by(|p, q| (p.0 - q.0).abs() <= EPSILON && (p.1 - q.1).abs() <= EPSILON);
+            assert_eq!(points[i].len(), expected.len(), "segment {}", i);
+            for (p, q) in points[i].iter().zip(&expected) {
+                assert!((p.0 - q.0).abs() < 1e-6 && (p.1 - q.1).abs() < 1e-6, "segment {}", i);
+            }
+        }
+    }
+
+    #[test]
+    fn many_crossings() {
+        // a grid of 5 horizontal and 5 vertical segments, and a diagonal through its crossings
+        let mut segments = Vec::new();
+        for i in 0..5 {
+            segments.push(((-1, 2 * i), (9, 2 * i)));
+            segments.push(((2 * i, -1), (2 * i, 9)));
+        }
+        segments.push(((-1, -1), (9, 9)));
+        let points = interior_intersections(&segments);
+        for (i, points) in points.iter().take(10).enumerate() {
+            assert_eq!(points.len(), 5, "segment {}", i);
+        }
+        let diagonal: Vec<_> = (0..5).map(|i| (2. * i as f64, 2. * i as f64)).collect();
+        assert_eq!(points[10], diagonal);
+    }
+}
@@ -1,53 +1,60 @@
+/// The line through two points, stored as a point and a direction so that vertical lines need no special case
 pub struct Line {
-    slope: Option<f64>,
     contact: (f64, f64),
-    intercept: Option<f64>,
+    direction: (f64, f64),
 }
 
 use geo::Point;
 
+use crate::geometry::RELATIVE_TOLERANCE;
+
 impl Line {
     pub fn from_points(a: &Point<f64>, b: &Point<f64>) -> Line {
-        let left = if a.x() <= b.x() { a } else { b };
-        let right = if a.x() <= b.x() { b } else { a };
-
-        let slope = (right.y() - left.y()) / (right.x() - left.x());
-        Line::new(slope, (a.x(), a.y()))
+        Line {
+            contact: (a.x(), a.y()),
+            direction: (b.x() - a.x(), b.y() - a.y()),
+        }
     }
 
-    pub fn new(slope: f64, contact: (f64, f64)) -> Line {
-        let s = if slope.is_infinite() {
-            None
-        } else {
-            Some(slope)
-        };
+    /// Returns whether both lines are the same, up to rounding errors. The line through two equal points overlaps
+    /// with every line through that point.
+    pub fn is_overlapping_with(&self, other: &Self) -> bool {
+        let cross = |u: (f64, f64), v: (f64, f64)| u.0 * v.1 - u.1 * v.0;
+        let length = |u: (f64, f64)| u.0.hypot(u.1);
+        let offset = (other.contact.0 - self.contact.0, other.contact.1 - self.contact.1);
+        let parallel = cross(self.direction, other.direction).abs()
+            <= RELATIVE_TOLERANCE * length(self.direction) * length(other.direction);
+        let through = cross(self.direction, offset).abs()
+            <= RELATIVE_TOLERANCE * length(self.direction) * length(offset)
+            && cross(other.direction, offset).abs() <= RELATIVE_TOLERANCE * length(other.direction) * length(offset);
+        parallel && through
+    }
+}
 
-        let intercept = if let Some(a) = s {
-            Some(contact.1 - a * contact.0)
-        } else {
-            None
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Line {
-            slope: s,
-            contact,
-            intercept,
-        }
+    fn line(a: (f64, f64), b: (f64, f64)) -> Line {
+        Line::from_points(&Point::from(a), &Point::from(b))
     }
 
-    pub fn is_overlapping_with(&self, other: &Self) -> bool {
-        if let Some(a1) = self.slope {
-            if let Some(a2) = other.slope {
-                a1 == a2 && self.intercept.unwrap() == other.intercept.unwrap()
-            } else {
-                false
-            }
-        } else {
-            if let Some(_) = other.slope {
-                other.is_overlapping_with(&self)
-            } else {
-                self.contact.0 == other.contact.0
-            }
-        }
+    #[test]
+    fn overlapping() {
+        // vertical, in both directions
+        assert!(line((2., 0.), (2., 1.)).is_overlapping_with(&line((2., 1.), (2., 5.))));
+        assert!(line((2., 5.), (2., 1.)).is_overlapping_with(&line((2., 1.), (2., 0.))));
+        assert!(!line((2., 0.), (2., 1.)).is_overlapping_with(&line((3., 1.), (3., 5.))));
+        assert!(!line((2., 0.), (2., 1.)).is_overlapping_with(&line((2., 1.), (3., 1.))));
+        // slopes and intercepts that differ by rounding errors
+        assert!(line((0.1, 0.3), (0.4, 1.2)).is_overlapping_with(&line((0.4, 1.2), (0.7, 2.1))));
+        let far = line((1e6, 1e6 + 0.1), (2e6, 2e6 + 0.1));
+        assert!(far.is_overlapping_with(&line((2e6, 2e6 + 0.1), (3e6, 3e6 + 0.1))));
+        assert!(!line((0., 0.), (1., 1.)).is_overlapping_with(&line((1., 1.), (2., 2.001))));
+        // parallel but apart
+        assert!(!line((0., 0.), (1., 1.)).is_overlapping_with(&line((0., 1.), (1., 2.))));
+        // repeated points
+        assert!(line((1., 1.), (1., 1.)).is_overlapping_with(&line((1., 1.), (4., 0.))));
+        assert!(line((0., 0.), (1., 1.)).is_overlapping_with(&line((1., 1.), (1., 1.))));
     }
 }
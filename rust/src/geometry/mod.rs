@@ -1,12 +1,22 @@
 mod dcel;
+mod descriptors;
 mod disk;
+mod intersections;
 mod line;
+mod overlap;
 mod polygon;
+mod simplify;
 
 pub use dcel::DCEL;
+pub use descriptors::{Descriptors, Rectangle};
 pub use disk::Disk;
+pub use intersections::{interior_intersections, intersections, Intersection, Segment};
 use line::Line;
 pub use polygon::Polygon;
+pub use simplify::{simplify_islands, Simplification};
+
+/// Cross products below this fraction of the product of the lengths involved are rounding errors
+const RELATIVE_TOLERANCE: f64 = 1e-12;
 
 fn dist(a: &(f64, f64), b: &(f64, f64)) -> f64 {
     let dx = a.0 - b.0;
@@ -78,11 +88,7 @@ fn trivial_disk(boundary: &Vec<(f64, f64)>) -> Option<Disk> {
 }
 
 /// Computes the smallest enclosing disk of points `enclosing` (`boundary` should initially be empty), returns `None` if it does not exist
-pub fn smallest_disk(
-    enclosing: &Vec<(f64, f64)>,
-    boundary: &mut Vec<(f64, f64)>,
-    len: usize,
-) -> Option<Disk> {
+pub fn smallest_disk(enclosing: &Vec<(f64, f64)>, boundary: &mut Vec<(f64, f64)>, len: usize) -> Option<Disk> {
     if len == 0 || boundary.len() == 3 {
         return trivial_disk(boundary);
     }
@@ -0,0 +1,188 @@
+/// Position of a point relative to a polygon
+#[derive(Debug, PartialEq)]
+enum Position {
+    Inside,
+    Outside,
+    /// On the boundary, on the edge with the given direction
+    Boundary((f64, f64)),
+}
+
+/// Returns the vertices of `ring` in counterclockwise order
+fn counterclockwise(ring: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut vertices = ring.to_vec();
+    if signed_area(&vertices) < 0. {
+        vertices.reverse();
+    }
+    vertices
+}
+
+fn signed_area(ring: &[(f64, f64)]) -> f64 {
+    let mut area = 0.;
+    for i in 0..ring.len() {
+        area += cross(ring[i], ring[(i + 1) % ring.len()]);
+    }
+    area / 2.
+}
+
+fn cross(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn sub(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn lerp(a: (f64, f64), b: (f64, f64), t: f64) -> (f64, f64) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// Classifies `point` with respect to the polygon `ring`, points within `eps` of an edge are on the boundary
+fn position(point: (f64, f64), ring: &[(f64, f64)], eps: f64) -> Position {
+    let mut inside = false;
+    for i in 0..ring.len() {
+        let a = ring[i];
+        let b = ring[(i + 1) % ring.len()];
+        let ab = sub(b, a);
+        let length = ab.0.hypot(ab.1);
+        if length > 0. {
+            let ap = sub(point, a);
+            let along = (ap.0 * ab.0 + ap.1 * ab.1) / length;
+            if (cross(ab, ap) / length).abs() <= eps && along >= -eps && along <= length + eps {
+                return Position::Boundary(ab);
+            }
+        }
+
+        if (a.1 > point.1) != (b.1 > point.1) {
+            let x = a.0 + (point.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+            if point.0 < x {
+                inside = !inside;
+            }
+        }
+    }
+
+    if inside {
+        Position::Inside
+    } else {
+        Position::Outside
+    }
+}
+
+/// Returns the parameters along the segment `p`-`q` where it meets the boundary of `ring`
+fn cuts(p: (f64, f64), q: (f64, f64), ring: &[(f64, f64)]) -> Vec<f64> {
+    let r = sub(q, p);
+    let length_squared = r.0 * r.0 + r.1 * r.1;
+    let mut params = vec![0., 1.];
+    for i in 0..ring.len() {
+        let a = ring[i];
+        let b = ring[(i + 1) % ring.len()];
+        let s = sub(b, a);
+        let denom = cross(r, s);
+        let ap = sub(a, p);
+        if denom == 0. {
+            // parallel, if collinear the endpoints of the edge cut the segment
+            if cross(ap, r) == 0. && length_squared > 0. {
+                for c in [a, b] {
+                    let cp = sub(c, p);
+                    params.push((cp.0 * r.0 + cp.1 * r.1) / length_squared);
+                }
+            }
+            continue;
+        }
+
+        let t = cross(ap, s) / denom;
+        let u = cross(ap, r) / denom;
+        if (0. ..=1.).contains(&u) {
+            params.push(t);
+        }
+    }
+
+    params.retain(|t| (0. ..=1.).contains(t));
+    params.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    params.dedup();
+    params
+}
+
+/// Adds twice the signed area swept by the parts of the boundary of `a` that bound the intersection with `b`.
+/// Parts on the shared boundary are only counted if `shared` is set and both boundaries run in the same direction.
+fn boundary_contribution(a: &[(f64, f64)], b: &[(f64, f64)], shared: bool, eps: f64) -> f64 {
+    let mut sum = 0.;
+    for i in 0..a.len() {
+        let p = a[i];
+        let q = a[(i + 1) % a.len()];
+        let params = cuts(p, q, b);
+        for window in params.windows(2) {
+            let start = lerp(p, q, window[0]);
+            let end = lerp(p, q, window[1]);
+            let include = match position(lerp(start, end, 0.5), b, eps) {
+                Position::Inside => true,
+                Position::Outside => false,
+                Position::Boundary(direction) => {
+                    let piece = sub(end, start);
+                    shared && piece.0 * direction.0 + piece.1 * direction.1 > 0.
+                }
+            };
+            if include {
+                sum += cross(start, end);
+            }
+        }
+    }
+    sum
+}
+
+/// Computes the area of the intersection of the simple polygons `a` and `b` (without holes), given by their vertices.
+/// The boundary of the intersection consists of the parts of each boundary that lie inside the other polygon,
+/// so its area follows from the shoelace formula over these parts.
+pub fn intersection_area(a: &[(f64, f64)], b: &[(f64, f64)]) -> f64 {
+    if a.len() < 3 || b.len() < 3 {
+        return 0.;
+    }
+
+    let a = counterclockwise(a);
+    let b = counterclockwise(b);
+
+    let scale = a.iter().chain(&b).fold(0f64, |m, p| m.max(p.0.abs()).max(p.1.abs()));
+    let eps = 1e-9 * scale.max(1.);
+
+    let sum = boundary_contribution(&a, &b, true, eps) + boundary_contribution(&b, &a, false, eps);
+    (sum / 2.).max(0.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64, size: f64) -> Vec<(f64, f64)> {
+        vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)]
+    }
+
+    #[test]
+    fn overlapping_squares() {
+        assert_eq!(intersection_area(&square(0., 0., 2.), &square(1., 1., 2.)), 1.);
+        assert_eq!(intersection_area(&square(0., 0., 2.), &square(5., 5., 2.)), 0.);
+    }
+
+    #[test]
+    fn nested_and_identical() {
+        assert_eq!(intersection_area(&square(0., 0., 4.), &square(1., 1., 1.)), 1.);
+        assert_eq!(intersection_area(&square(0., 0., 4.), &square(0., 0., 4.)), 16.);
+        let mut reversed = square(0., 0., 4.);
+        reversed.reverse();
+        assert_eq!(intersection_area(&square(0., 0., 4.), &reversed), 16.);
+    }
+
+    #[test]
+    fn shared_edges() {
+        // adjacent squares only share an edge
+        assert_eq!(intersection_area(&square(0., 0., 2.), &square(2., 0., 2.)), 0.);
+        // a rectangle covering the left half shares three edges with the square
+        let half = vec![(0., 0.), (1., 0.), (1., 2.), (0., 2.)];
+        assert_eq!(intersection_area(&square(0., 0., 2.), &half), 2.);
+        assert_eq!(intersection_area(&half, &square(0., 0., 2.)), 2.);
+    }
+
+    #[test]
+    fn non_convex() {
+        let l_shape = vec![(0., 0.), (3., 0.), (3., 1.), (1., 1.), (1., 3.), (0., 3.)];
+        assert_eq!(intersection_area(&l_shape, &square(0., 0., 2.)), 3.);
+    }
+}
@@ -1,36 +1,53 @@
+use std::io::{Result, Write};
 use std::iter::FromIterator;
 
 use geo::algorithm::contains::Contains;
+use geo::algorithm::orient::{Direction, Orient};
 use geo::point;
-use geo::prelude::Centroid;
+use geo::prelude::{Area, BoundingRect, Centroid, ConvexHull, EuclideanDistance, EuclideanLength};
 use geo::LineString;
 use geo::Point;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::Rng;
+use serde_json::{json, Value};
 
-use crate::geometry::smallest_disk;
+use crate::geometry::{
+    descriptors::minimum_area_rectangle, overlap::intersection_area, simplify_islands, smallest_disk, Descriptors,
+    Disk, Line, Rectangle, Simplification,
+};
 
 pub struct Polygon {
     vertices: Vec<(f64, f64)>,
     polygon: geo::Polygon<f64>,
+    /// Smallest weight of the network edges on the boundary, `NAN` if unknown
+    boundary_weight: f64,
 }
 
-use crate::geometry::Line;
-
 impl Polygon {
     /// Constructs a new Polygon
     pub fn new(vertices: Vec<(f64, f64)>) -> Polygon {
         let simplified = Polygon::simplify(vertices);
-        let line_string = LineString::from_iter(
-            simplified
-                .clone()
-                .into_iter()
-                .map(|(x, y)| Point::new(x, y)),
-        );
+        let line_string = LineString::from_iter(simplified.clone().into_iter().map(|(x, y)| Point::new(x, y)));
         let polygon = geo::Polygon::new(line_string, vec![]);
         Polygon {
             vertices: simplified,
             polygon,
+            boundary_weight: f64::NAN,
+        }
+    }
+
+    /// Sets the smallest weight of the network edges on the boundary
+    pub fn with_boundary_weight(mut self, weight: f64) -> Polygon {
+        self.boundary_weight = weight;
+        self
+    }
+
+    /// Returns the smallest weight of the network edges on the boundary, if known
+    pub fn min_boundary_weight(&self) -> Option<f64> {
+        if self.boundary_weight.is_nan() {
+            None
+        } else {
+            Some(self.boundary_weight)
         }
     }
 
@@ -65,6 +82,38 @@ impl Polygon {
         self.polygon.contains(&point!(x: point.0, y: point.1))
     }
 
+    /// Returns the distance from `point` to `self`, zero if `self` contains the point
+    pub fn distance_to_point(&self, point: &(f64, f64)) -> f64 {
+        point!(x: point.0, y: point.1).euclidean_distance(&self.polygon)
+    }
+
+    /// Returns the distance between `self` and `other`, zero if they intersect
+    pub fn distance(&self, other: &Polygon) -> f64 {
+        // the distance of polygons is attained at a vertex of one of them unless their boundaries cross,
+        // geo's rotating calipers for convex polygons does not terminate for some inputs so it is not used
+        let (a, b) = (&self.vertices, &other.vertices);
+        if a.is_empty() || b.is_empty() {
+            return f64::NAN;
+        }
+        if self.contains(&b[0]) || other.contains(&a[0]) {
+            return 0.;
+        }
+
+        let mut distance = f64::INFINITY;
+        for (ring, points) in [(a, b), (b, a)] {
+            for i in 0..ring.len() {
+                let segment = geo::Line::new(ring[i], ring[(i + 1) % ring.len()]);
+                for point in points {
+                    distance = distance.min(point!(x: point.0, y: point.1).euclidean_distance(&segment));
+                }
+            }
+        }
+        if distance > 0. && self.overlap_area(other) > 0. {
+            return 0.;
+        }
+        distance
+    }
+
     /// Returns the centroid of all the vertices
     pub fn centroid(&self) -> Option<(f64, f64)> {
         if let Some(c) = self.polygon.centroid() {
@@ -73,41 +122,191 @@ impl Polygon {
         None
     }
 
-    /// Returns the smallest enclosing disk of the vertices
-    pub fn smallest_disk_centroid(&self) -> Option<(f64, f64)> {
+    /// Returns the centroid of the smallest enclosing disk of the vertices, the vertices are shuffled using `rng`
+    pub fn smallest_disk_centroid<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(f64, f64)> {
+        self.smallest_disk(rng).map(|disk| disk.centroid())
+    }
+
+    /// Returns the smallest enclosing disk of the vertices, the vertices are shuffled using `rng`
+    pub fn smallest_disk<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Disk> {
         let mut enclosing = self.vertices.clone();
-        enclosing.shuffle(&mut thread_rng());
+        enclosing.shuffle(rng);
         let len = enclosing.len();
 
         let mut boundary = Vec::new();
+        smallest_disk(&enclosing, &mut boundary, len)
+    }
+
+    /// Returns the vertices of the boundary
+    pub fn vertices(&self) -> &[(f64, f64)] {
+        &self.vertices
+    }
+
+    /// Returns the area enclosed by the boundary
+    pub fn area(&self) -> f64 {
+        self.polygon.unsigned_area()
+    }
+
+    /// Returns the length of the boundary
+    pub fn perimeter(&self) -> f64 {
+        self.polygon.exterior().euclidean_length()
+    }
+
+    /// Returns the lower left and upper right corner of the axis-aligned bounding box
+    pub fn bounding_box(&self) -> Option<((f64, f64), (f64, f64))> {
+        self.polygon
+            .bounding_rect()
+            .map(|rect| (rect.min().x_y(), rect.max().x_y()))
+    }
+
+    /// Returns the convex hull of the vertices
+    pub fn convex_hull(&self) -> Polygon {
+        let hull = self.polygon.convex_hull();
+        let mut vertices: Vec<_> = hull.exterior().points_iter().map(|p| p.x_y()).collect();
+        // the exterior of a geo polygon is closed
+        vertices.pop();
+        Polygon::new(vertices)
+    }
+
+    /// Returns the boundary simplified with `method` and `tolerance`, without crossing itself, see
+    /// [`simplify_islands`] to simplify neighbouring islands together
+    pub fn simplified(&self, method: Simplification, tolerance: f64) -> Polygon {
+        simplify_islands(std::slice::from_ref(self), method, tolerance).remove(0)
+    }
+
+    /// Returns the ratio between the area and the area of the convex hull
+    pub fn convexity(&self) -> f64 {
+        self.convexity_within(&self.convex_hull())
+    }
 
-        match smallest_disk(&enclosing, &mut boundary, len) {
-            Some(disk) => Some(disk.centroid()),
-            None => None,
+    /// Returns the ratio between the area and the area of `hull`, the convex hull of `self`
+    fn convexity_within(&self, hull: &Polygon) -> f64 {
+        let hull_area = hull.area();
+        if hull_area == 0. {
+            1.
+        } else {
+            self.area() / hull_area
         }
     }
 
-    #[allow(dead_code)]
-    pub fn to_ipe(&self) {
-        println!("<path layer=\"alpha\" stroke=\"black\">");
+    /// Returns the isoperimetric quotient `4 pi A / P^2`
+    pub fn compactness(&self) -> f64 {
+        let perimeter = self.perimeter();
+        if perimeter == 0. {
+            0.
+        } else {
+            4. * std::f64::consts::PI * self.area() / (perimeter * perimeter)
+        }
+    }
+
+    /// Returns the minimum-area bounding rectangle
+    pub fn minimum_area_rectangle(&self) -> Option<Rectangle> {
+        minimum_area_rectangle(self.convex_hull().vertices())
+    }
+
+    /// Returns the area of the intersection of `self` and `other`
+    pub fn overlap_area(&self, other: &Polygon) -> f64 {
+        intersection_area(&self.vertices, &other.vertices)
+    }
+
+    /// Returns whether the bounding boxes of `self` and `other` intersect
+    pub fn bounding_boxes_intersect(&self, other: &Polygon) -> bool {
+        match (self.bounding_box(), other.bounding_box()) {
+            (Some((a_min, a_max)), Some((b_min, b_max))) => {
+                a_min.0 <= b_max.0 && b_min.0 <= a_max.0 && a_min.1 <= b_max.1 && b_min.1 <= a_max.1
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns all the shape descriptors at once, the smallest enclosing disk uses `rng`
+    pub fn descriptors<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Descriptors> {
+        let area = self.area();
+        let perimeter = self.perimeter();
+        let hull = self.convex_hull();
+        let rectangle = minimum_area_rectangle(hull.vertices())?;
+        let disk = self.smallest_disk(rng)?;
+
+        Some(Descriptors {
+            area,
+            perimeter,
+            centroid: self.centroid()?,
+            bounding_box: self.bounding_box()?,
+            convexity: self.convexity_within(&hull),
+            compactness: self.compactness(),
+            elongation: if rectangle.length == 0. {
+                0.
+            } else {
+                1. - rectangle.width / rectangle.length
+            },
+            orientation: rectangle.orientation,
+            disk_center: disk.centroid(),
+            disk_radius: disk.radius(),
+        })
+    }
+
+    /// Returns the boundary as a GeoJSON `Polygon` geometry, whose ring is counter-clockwise and repeats the first
+    /// vertex at the end
+    pub fn to_geojson(&self) -> Value {
+        let oriented = self.polygon.orient(Direction::Default);
+        let ring: Vec<_> = oriented
+            .exterior()
+            .points_iter()
+            .map(|p| json!([p.x(), p.y()]))
+            .collect();
+        json!({"type": "Polygon", "coordinates": [ring]})
+    }
+
+    /// Returns the counter-clockwise boundary as a WKT `POLYGON`
+    pub fn to_wkt(&self) -> String {
+        let oriented = self.polygon.orient(Direction::Default);
+        let ring: Vec<_> = oriented
+            .exterior()
+            .points_iter()
+            .map(|p| format!("{} {}", p.x(), p.y()))
+            .collect();
+        format!("POLYGON (({}))", ring.join(", "))
+    }
+
+    /// Returns the counter-clockwise boundary as a little-endian WKB polygon
+    pub fn to_wkb(&self) -> Vec<u8> {
+        let oriented = self.polygon.orient(Direction::Default);
+        let ring = oriented.exterior();
+        let mut wkb = vec![1];
+        wkb.extend_from_slice(&3u32.to_le_bytes());
+        wkb.extend_from_slice(&1u32.to_le_bytes());
+        wkb.extend_from_slice(&(ring.0.len() as u32).to_le_bytes());
+        for point in ring.points_iter() {
+            wkb.extend_from_slice(&point.x().to_le_bytes());
+            wkb.extend_from_slice(&point.y().to_le_bytes());
+        }
+        wkb
+    }
+
+    /// Writes the boundary as an Ipe path
+    pub fn write_ipe<W: Write>(&self, out: &mut W) -> Result<()> {
+        writeln!(out, "<path layer=\"alpha\" stroke=\"black\">")?;
         let boundary: Vec<_> = self.polygon.exterior().points_iter().collect();
         for i in 0..boundary.len() {
             let x = boundary[i].x();
             let y = boundary[i].y();
             if i == 0 {
-                println!("{} {} m", x, y);
+                writeln!(out, "{} {} m", x, y)?;
             } else {
-                println!("{} {} l", x, y);
+                writeln!(out, "{} {} l", x, y)?;
             }
         }
-        println!("h");
-        println!("</path>");
+        writeln!(out, "h")?;
+        writeln!(out, "</path>")?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
 
     #[test]
     fn contains() {
@@ -124,14 +323,83 @@ mod tests {
 
     #[test]
     fn smallest_disk_centroid() {
+        let polygon = Polygon::new(vec![(0., 0.), (1., 0.), (1., 0.5), (0.25, 0.25), (0.5, 1.), (0., 1.)]);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(polygon.smallest_disk_centroid(&mut rng), Some((0.5, 0.5)));
+    }
+
+    #[test]
+    fn descriptors() {
+        let polygon = Polygon::new(vec![(0., 0.), (4., 0.), (4., 1.), (0., 1.)]);
+        let mut rng = StdRng::seed_from_u64(0);
+        let descriptors = polygon.descriptors(&mut rng).unwrap();
+        assert_eq!(descriptors.area, 4.);
+        assert_eq!(descriptors.perimeter, 10.);
+        assert_eq!(descriptors.centroid, (2., 0.5));
+        assert_eq!(descriptors.bounding_box, ((0., 0.), (4., 1.)));
+        assert_eq!(descriptors.convexity, 1.);
+        assert!((descriptors.compactness - 16. * std::f64::consts::PI / 100.).abs() < 1e-9);
+        assert_eq!(descriptors.elongation, 0.75);
+        assert_eq!(descriptors.orientation, 0.);
+        assert_eq!(descriptors.disk_center, (2., 0.5));
+        assert!((descriptors.disk_radius - 17f64.sqrt() / 2.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn convexity() {
+        let polygon = Polygon::new(vec![(0., 0.), (2., 0.), (2., 2.), (1., 1.), (0., 2.)]);
+        assert_eq!(polygon.convex_hull().area(), 4.);
+        assert_eq!(polygon.convexity(), 0.75);
+    }
+
+    #[test]
+    fn collinear() {
+        // vertical runs in both directions, a repeated vertex and slopes that differ by rounding errors
         let polygon = Polygon::new(vec![
             (0., 0.),
-            (1., 0.),
-            (1., 0.5),
-            (0.25, 0.25),
-            (0.5, 1.),
             (0., 1.),
+            (0., 2.),
+            (0.1, 2.3),
+            (0.2, 2.6),
+            (1., 2.),
+            (1., 2.),
+            (1., 1.),
+            (1., 0.),
         ]);
-        assert_eq!(polygon.smallest_disk_centroid(), Some((0.5, 0.5)));
+        assert_eq!(
+            polygon.vertices(),
+            &[(0., 0.), (0., 2.), (0.2, 2.6), (1., 2.), (1., 0.)]
+        );
+    }
+
+    #[test]
+    fn geojson() {
+        let polygon = Polygon::new(vec![(0., 0.), (0., 1.), (1., 0.)]);
+        assert_eq!(
+            polygon.to_geojson(),
+            json!({"type": "Polygon", "coordinates": [[[0., 0.], [1., 0.], [0., 1.], [0., 0.]]]})
+        );
+    }
+
+    #[test]
+    fn wkt() {
+        let polygon = Polygon::new(vec![(0., 0.), (0., 1.), (1.5, 0.)]);
+        assert_eq!(polygon.to_wkt(), "POLYGON ((0 0, 1.5 0, 0 1, 0 0))");
+        let wkb = polygon.to_wkb();
+        assert_eq!(wkb.len(), 1 + 4 + 4 + 4 + 4 * 16);
+        assert_eq!(wkb[..13], [1, 3, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0]);
+        assert_eq!(wkb[21..29], 0f64.to_le_bytes());
+        assert_eq!(wkb[29..37], 1.5f64.to_le_bytes());
+    }
+
+    #[test]
+    fn distance() {
+        let square = Polygon::new(vec![(0., 0.), (2., 0.), (2., 2.), (0., 2.)]);
+        let far = Polygon::new(vec![(-9., -9.), (-1., -9.), (-1., -1.), (-9., -1.)]);
+        let cross = Polygon::new(vec![(-1., 0.5), (3., 0.5), (3., 1.5), (-1., 1.5)]);
+        assert_eq!(square.distance(&far), 2f64.sqrt());
+        assert_eq!(square.distance(&cross), 0.);
+        assert_eq!(square.distance_to_point(&(1., 1.)), 0.);
+        assert_eq!(square.distance_to_point(&(1., 3.)), 1.);
     }
 }
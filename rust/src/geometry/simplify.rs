@@ -0,0 +1,448 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::str::FromStr;
+
+use fxhash::{FxHashMap, FxHashSet};
+use ordered_float::OrderedFloat;
+
+use crate::geometry::{Polygon, RELATIVE_TOLERANCE};
+
+type Point = (f64, f64);
+
+/// How [`simplify_islands`] removes vertices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Simplification {
+    /// Douglas–Peucker, the tolerance is the largest distance from a removed vertex to the simplified boundary
+    DouglasPeucker,
+    /// Visvalingam–Whyatt, the tolerance is the largest area of the triangle a removed vertex forms with its
+    /// neighbours
+    VisvalingamWhyatt,
+}
+
+impl FromStr for Simplification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "douglas-peucker" | "dp" => Ok(Simplification::DouglasPeucker),
+            "visvalingam-whyatt" | "vw" => Ok(Simplification::VisvalingamWhyatt),
+            _ => Err(format!(
+                "unknown simplification {}, expected douglas-peucker (dp) or visvalingam-whyatt (vw)",
+                s
+            )),
+        }
+    }
+}
+
+/// Simplifies the boundaries of the islands of a layer, keeping the topology: the simplified islands neither
+/// overlap nor touch where they did not before, boundaries shared by neighbouring islands stay shared, and no
+/// boundary crosses itself. The islands are returned in the same order, with the same boundary weights.
+///
+/// The boundaries are cut into chains at the vertices where three or more boundary edges meet, a boundary meeting no
+/// other is a closed chain from one of its vertices back to it, and every chain is simplified once with its ends
+/// fixed. A chain is only replaced by a shortcut when no vertex of the current boundaries lies in the region between
+/// them, so no other boundary can cross the shortcut, and when no other boundary already joins the ends of the
+/// shortcut. Vertices are therefore kept beyond the tolerance where removing them would break the topology.
+pub fn simplify_islands(islands: &[Polygon], method: Simplification, tolerance: f64) -> Vec<Polygon> {
+    let mut boundaries = Boundaries::new(islands);
+    for chain in boundaries.chains() {
+        match method {
+            Simplification::DouglasPeucker => boundaries.douglas_peucker(&chain, tolerance),
+            Simplification::VisvalingamWhyatt => boundaries.visvalingam_whyatt(&chain, tolerance),
+        }
+    }
+    islands
+        .iter()
+        .zip(&boundaries.rings)
+        .map(|(island, ring)| {
+            let vertices: Vec<_> = ring
+                .iter()
+                .filter(|&&i| boundaries.alive[i])
+                .map(|&i| boundaries.points[i])
+                .collect();
+            let vertices = if vertices.len() < 3 {
+                island.vertices().to_vec()
+            } else {
+                vertices
+            };
+            Polygon::new(vertices).with_boundary_weight(island.min_boundary_weight().unwrap_or(f64::NAN))
+        })
+        .collect()
+}
+
+/// The boundaries of all islands, with the vertices they share merged
+struct Boundaries {
+    points: Vec<Point>,
+    /// The vertices of every island, indices into `points`
+    rings: Vec<Vec<usize>>,
+    /// The neighbours of every vertex along the boundaries
+    adj: Vec<Vec<usize>>,
+    /// Whether every vertex is still on the simplified boundaries
+    alive: Vec<bool>,
+    /// The edges of the simplified boundaries, by their ends with the smaller index first
+    segments: FxHashSet<(usize, usize)>,
+    /// The vertices by the cell of a grid they lie in
+    cells: FxHashMap<(i64, i64), Vec<usize>>,
+    cell: f64,
+}
+
+impl Boundaries {
+    fn new(islands: &[Polygon]) -> Boundaries {
+        let mut points = Vec::new();
+        let mut indices = FxHashMap::default();
+        let mut rings = Vec::new();
+        for island in islands {
+            let mut ring: Vec<usize> = Vec::new();
+            for &(x, y) in island.vertices() {
+                let index = *indices.entry((x.to_bits(), y.to_bits())).or_insert_with(|| {
+                    points.push((x, y));
+                    points.len() - 1
+                });
+                if ring.last() != Some(&index) {
+                    ring.push(index);
+                }
+            }
+            if ring.len() > 1 && ring.first() == ring.last() {
+                ring.pop();
+            }
+            rings.push(ring);
+        }
+
+        let mut adj = vec![Vec::new(); points.len()];
+        let mut segments = FxHashSet::default();
+        for ring in &rings {
+            if ring.len() < 2 {
+                continue;
+            }
+            for (k, &a) in ring.iter().enumerate() {
+                let b = ring[(k + 1) % ring.len()];
+                if segments.insert(key(a, b)) {
+                    adj[a].push(b);
+                    adj[b].push(a);
+                }
+            }
+        }
+
+        // cells about twice as wide as the vertices are apart on average
+        let (mut min, mut max) = ((f64::INFINITY, f64::INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY));
+        for &(x, y) in &points {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        let mut cell = 2. * ((max.0 - min.0).max(1e-9) * (max.1 - min.1).max(1e-9) / points.len().max(1) as f64).sqrt();
+        if !cell.is_finite() || cell <= 0. {
+            cell = 1.;
+        }
+        let mut cells: FxHashMap<(i64, i64), Vec<usize>> = FxHashMap::default();
+        for (i, &point) in points.iter().enumerate() {
+            cells.entry(grid_cell(point, cell)).or_default().push(i);
+        }
+
+        Boundaries {
+            alive: vec![true; points.len()],
+            points,
+            rings,
+            adj,
+            segments,
+            cells,
+            cell,
+        }
+    }
+
+    /// Returns the chains of vertices between the vertices where three or more boundary edges meet, and the closed
+    /// chains of boundaries without such vertices, which start and end at their first vertex
+    fn chains(&self) -> Vec<Vec<usize>> {
+        let junction = |v: usize| self.adj[v].len() != 2;
+        let mut visited = FxHashSet::default();
+        let mut chains = Vec::new();
+        let walk = |start: usize, next: usize, visited: &mut FxHashSet<(usize, usize)>| {
+            let mut chain = vec![start];
+            let (mut previous, mut current) = (start, next);
+            visited.insert(key(previous, current));
+            loop {
+                chain.push(current);
+                if current == start || junction(current) {
+                    break;
+                }
+                let next = if self.adj[current][0] == previous {
+                    self.adj[current][1]
+                } else {
+                    self.adj[current][0]
+                };
+                visited.insert(key(current, next));
+                previous = current;
+                current = next;
+            }
+            chain
+        };
+        for v in (0..self.points.len()).filter(|&v| junction(v)) {
+            for &w in &self.adj[v] {
+                if !visited.contains(&key(v, w)) {
+                    chains.push(walk(v, w, &mut visited));
+                }
+            }
+        }
+        for v in 0..self.points.len() {
+            if !junction(v) && !visited.contains(&key(v, self.adj[v][0])) {
+                chains.push(walk(v, self.adj[v][0], &mut visited));
+            }
+        }
+        chains
+    }
+
+    /// Simplifies `chain` with the Douglas–Peucker algorithm: the chain is replaced by the shortcut between its ends
+    /// if no vertex is farther than `tolerance` from it, otherwise the chain is split at its farthest vertex and both
+    /// parts are simplified in turn. Closed chains are split twice regardless, so they keep at least four vertices.
+    fn douglas_peucker(&mut self, chain: &[usize], tolerance: f64) {
+        let forced: u8 = if chain.first() == chain.last() { 2 } else { 0 };
+        let mut stack = vec![(0, chain.len() - 1, forced)];
+        while let Some((i, j, forced)) = stack.pop() {
+            if j <= i + 1 {
+                continue;
+            }
+            let (a, b) = (self.points[chain[i]], self.points[chain[j]]);
+            let (mut farthest, mut distance) = (i + 1, f64::NEG_INFINITY);
+            for (k, &vertex) in chain.iter().enumerate().take(j).skip(i + 1) {
+                let d = segment_distance(self.points[vertex], a, b);
+                if d > distance {
+                    farthest = k;
+                    distance = d;
+                }
+            }
+            if forced == 0 && distance <= tolerance && self.can_shortcut(&chain[i..=j]) {
+                self.shortcut(&chain[i..=j]);
+            } else {
+                let forced = forced.saturating_sub(1);
+                stack.push((farthest, j, forced));
+                stack.push((i, farthest, forced));
+            }
+        }
+    }
+
+    /// Simplifies `chain` with the Visvalingam–Whyatt algorithm: the vertex forming the smallest triangle with its
+    /// neighbours is removed until every triangle has an area of at least `tolerance`. Closed chains keep at least
+    /// three vertices.
+    fn visvalingam_whyatt(&mut self, chain: &[usize], tolerance: f64) {
+        let n = chain.len();
+        let mut interior = n.saturating_sub(2);
+        let minimum = if chain.first() == chain.last() { 2 } else { 0 };
+        let mut previous: Vec<usize> = (0..n).map(|k| k.saturating_sub(1)).collect();
+        let mut next: Vec<usize> = (0..n).map(|k| k + 1).collect();
+        let mut areas = vec![f64::INFINITY; n];
+        let mut heap = BinaryHeap::new();
+        for k in 1..n.saturating_sub(1) {
+            areas[k] = self.triangle_area(chain[k - 1], chain[k], chain[k + 1]);
+            heap.push(Reverse((OrderedFloat(areas[k]), k)));
+        }
+        while let Some(Reverse((OrderedFloat(area), k))) = heap.pop() {
+            if area >= tolerance || interior <= minimum {
+                break;
+            }
+            if area != areas[k] {
+                // removed, or its triangle changed since
+                continue;
+            }
+            let (p, q) = (previous[k], next[k]);
+            areas[k] = f64::NAN;
+            if !self.can_shortcut(&[chain[p], chain[k], chain[q]]) {
+                continue;
+            }
+            self.shortcut(&[chain[p], chain[k], chain[q]]);
+            interior -= 1;
+            next[p] = q;
+            previous[q] = p;
+            for m in [p, q] {
+                if m != 0 && m != n - 1 {
+                    areas[m] = self.triangle_area(chain[previous[m]], chain[m], chain[next[m]]);
+                    heap.push(Reverse((OrderedFloat(areas[m]), m)));
+                }
+            }
+        }
+    }
+
+    fn triangle_area(&self, a: usize, b: usize, c: usize) -> f64 {
+        let (a, b, c) = (self.points[a], self.points[b], self.points[c]);
+        ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)).abs() / 2.
+    }
+
+    /// Returns whether the path of vertices `path` can be replaced by the segment between its ends without changing
+    /// the topology of the boundaries
+    fn can_shortcut(&self, path: &[usize]) -> bool {
+        let (first, last) = (path[0], path[path.len() - 1]);
+        if self.segments.contains(&key(first, last)) {
+            return false;
+        }
+        let region: Vec<_> = path.iter().map(|&i| self.points[i]).collect();
+        let (mut min, mut max) = (region[0], region[0]);
+        for &(x, y) in &region {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        let members: FxHashSet<usize> = path.iter().copied().collect();
+        let ((column_min, row_min), (column_max, row_max)) = (grid_cell(min, self.cell), grid_cell(max, self.cell));
+        for column in column_min..=column_max {
+            for row in row_min..=row_max {
+                for &i in self.cells.get(&(column, row)).into_iter().flatten() {
+                    if self.alive[i] && !members.contains(&i) && inside_or_on(&region, self.points[i]) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Replaces the path of vertices `path` by the segment between its ends
+    fn shortcut(&mut self, path: &[usize]) {
+        for pair in path.windows(2) {
+            self.segments.remove(&key(pair[0], pair[1]));
+        }
+        for &i in &path[1..path.len() - 1] {
+            self.alive[i] = false;
+        }
+        self.segments.insert(key(path[0], path[path.len() - 1]));
+    }
+}
+
+fn key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn grid_cell((x, y): Point, cell: f64) -> (i64, i64) {
+    ((x / cell).floor() as i64, (y / cell).floor() as i64)
+}
+
+/// Returns the distance from `p` to the segment between `a` and `b`
+fn segment_distance(p: Point, a: Point, b: Point) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0. {
+        0.
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0., 1.)
+    };
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// Returns whether `p` lies inside the closed polygon `region`, by the even-odd rule, or on its boundary
+fn inside_or_on(region: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    for (k, &a) in region.iter().enumerate() {
+        let b = region[(k + 1) % region.len()];
+        let (u, v) = ((b.0 - a.0, b.1 - a.1), (p.0 - a.0, p.1 - a.1));
+        let cross = u.0 * v.1 - u.1 * v.0;
+        let within = p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1);
+        if within && cross.abs() <= RELATIVE_TOLERANCE * u.0.hypot(u.1) * v.0.hypot(v.1) {
+            return true;
+        }
+        if (a.1 > p.1) != (b.1 > p.1) && p.0 < a.0 + (p.1 - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A staircase from (0, 0) to (n, n) followed by the corner `corner`
+    fn staircase(n: usize, corner: Point) -> Vec<Point> {
+        let mut vertices = Vec::new();
+        for i in 0..n {
+            vertices.push((i as f64, i as f64));
+            vertices.push((i as f64 + 1., i as f64));
+        }
+        vertices.push((n as f64, n as f64));
+        vertices.push(corner);
+        vertices
+    }
+
+    #[test]
+    fn staircase_island() {
+        let island = Polygon::new(staircase(20, (0., 20.)));
+        assert_eq!(island.vertices().len(), 42);
+        for method in [Simplification::DouglasPeucker, Simplification::VisvalingamWhyatt] {
+            let simplified = island.simplified(method, 1.);
+            assert!(
+                simplified.vertices().len() < 10,
+                "{:?}: {:?}",
+                method,
+                simplified.vertices()
+            );
+            assert!((simplified.area() - island.area()).abs() < 20., "{:?}", method);
+            // the island keeps its vertices
+            for vertex in simplified.vertices() {
+                assert!(island.vertices().contains(vertex));
+            }
+        }
+
+        let simplified = island.simplified(Simplification::DouglasPeucker, 0.5);
+        for &vertex in island.vertices() {
+            assert!(simplified.distance_to_point(&vertex) <= 0.5 + 1e-9, "{:?}", vertex);
+        }
+        // the small steps are kept
+        let simplified = island.simplified(Simplification::VisvalingamWhyatt, 0.4);
+        assert_eq!(simplified.vertices().len(), 42);
+    }
+
+    #[test]
+    fn shared_boundary() {
+        // two islands sharing the staircase, which is simplified the same way for both
+        let mut upper = staircase(10, (-5., 15.));
+        upper.reverse();
+        let islands = [Polygon::new(staircase(10, (15., -5.))), Polygon::new(upper)];
+        let area = islands[0].area() + islands[1].area();
+        for method in [Simplification::DouglasPeucker, Simplification::VisvalingamWhyatt] {
+            let simplified = simplify_islands(&islands, method, 2.);
+            assert!(
+                simplified[0].vertices().len() < islands[0].vertices().len(),
+                "{:?}",
+                method
+            );
+            assert!(
+                (simplified[0].area() + simplified[1].area() - area).abs() < 1e-9,
+                "{:?}",
+                method
+            );
+            assert_eq!(simplified[0].overlap_area(&simplified[1]), 0., "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn topology() {
+        // a small island in a notch of a larger one, which would overlap it if the larger one was simplified alone
+        let outer = || Polygon::new(vec![(0., 0.), (10., 0.), (10., 1.), (5., 1.2), (0., 1.)]);
+        let inner = || Polygon::new(vec![(4., 0.5), (6., 0.5), (5., 1.1)]);
+        for (method, tolerance) in [
+            (Simplification::DouglasPeucker, 0.5),
+            (Simplification::VisvalingamWhyatt, 2.),
+        ] {
+            let simplified = simplify_islands(&[outer(), inner()], method, tolerance);
+            assert_eq!(simplified[0].vertices().len(), 5, "{:?}", method);
+            assert_eq!(simplified[1].vertices().len(), 3, "{:?}", method);
+            let alone = outer().simplified(method, tolerance);
+            assert_eq!(alone.vertices().len(), 4, "{:?}", method);
+        }
+
+        // a thin island between two others, whose boundaries with them have the same ends, does not collapse
+        let islands = [
+            Polygon::new(vec![(0., 0.), (5., 0.1), (10., 0.), (10., 5.), (0., 5.)]),
+            Polygon::new(vec![(0., 0.), (0., -5.), (10., -5.), (10., 0.), (5., -0.1)]),
+            Polygon::new(vec![(0., 0.), (5., 0.1), (10., 0.), (5., -0.1)]),
+        ];
+        for method in [Simplification::DouglasPeucker, Simplification::VisvalingamWhyatt] {
+            let simplified = simplify_islands(&islands, method, 1.);
+            assert_eq!(simplified[0].vertices().len(), 4, "{:?}", method);
+            assert_eq!(simplified[1].vertices().len(), 5, "{:?}", method);
+            assert_eq!(simplified[2].area(), 0.5, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("dp".parse(), Ok(Simplification::DouglasPeucker));
+        assert_eq!("visvalingam-whyatt".parse(), Ok(Simplification::VisvalingamWhyatt));
+        assert!("bezier".parse::<Simplification>().is_err());
+    }
+}
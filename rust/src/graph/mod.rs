@@ -1,10 +1,101 @@
-use fxhash::FxHashMap;
+use std::fmt;
+use std::io::{Result, Write};
+
+use crate::geometry::interior_intersections;
 use crate::geometry::Polygon;
 use crate::geometry::DCEL;
+use fxhash::FxHashMap;
+
+/// Number of times the intersections are split at most by [`Graph::planarize`]
+const PLANARIZE_PASSES: usize = 8;
+
+/// Optional steps that clean a network before its islands are extracted, see [`Graph::clean`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Cleaning {
+    /// Distance within which the ends of edges are merged with other vertices, see [`Graph::snap`], 0 to keep them
+    pub snap: f64,
+    /// Splits the edges where they cross, see [`Graph::planarize`]
+    pub planarize: bool,
+}
+
+/// What [`Graph::clean`] changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CleaningReport {
+    /// Number of vertices merged into other vertices by [`Graph::snap`]
+    pub merged: usize,
+    /// Number of times an edge was split by [`Graph::planarize`]
+    pub splits: usize,
+}
+
+impl CleaningReport {
+    pub fn is_empty(&self) -> bool {
+        self.merged == 0 && self.splits == 0
+    }
+}
+
+impl fmt::Display for CleaningReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let vertices = if self.merged == 1 { "vertex" } else { "vertices" };
+        let times = if self.splits == 1 { "time" } else { "times" };
+        write!(
+            f,
+            "merged {} {}, split edges {} {}",
+            self.merged, vertices, self.splits, times
+        )
+    }
+}
+
+/// Disjoint sets of the indices `0..len`, merged by [`UnionFind::union`]
+pub(crate) struct UnionFind {
+    parents: Vec<usize>,
+}
 
+impl UnionFind {
+    pub(crate) fn new(len: usize) -> UnionFind {
+        UnionFind {
+            parents: (0..len).collect(),
+        }
+    }
+
+    /// Adds a set of its own for the next index, and returns that index
+    pub(crate) fn push(&mut self) -> usize {
+        self.parents.push(self.parents.len());
+        self.parents.len() - 1
+    }
+
+    /// Returns the root of the set of `x`, halving the path to it
+    pub(crate) fn find(&mut self, mut x: usize) -> usize {
+        while self.parents[x] != x {
+            self.parents[x] = self.parents[self.parents[x]];
+            x = self.parents[x];
+        }
+        x
+    }
+
+    /// Merges the sets of `a` and `b`
+    pub(crate) fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a] = b;
+    }
+
+    /// Returns the number of disjoint sets
+    pub(crate) fn sets(&self) -> usize {
+        (0..self.parents.len()).filter(|&x| self.parents[x] == x).count()
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Graph {
     vertices: Vec<(i32, i32)>,
     adj: Vec<Vec<usize>>,
+    /// Weights of the edges, `weights[i][j]` is the weight of the edge to `adj[i][j]`
+    weights: Vec<Vec<f64>>,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Graph::new()
+    }
 }
 
 impl Graph {
@@ -12,29 +103,42 @@ impl Graph {
         Graph {
             vertices: Vec::new(),
             adj: Vec::new(),
+            weights: Vec::new(),
         }
     }
 
     pub fn add_vertex(&mut self, vertex: (i32, i32)) {
         self.vertices.push(vertex);
         self.adj.push(Vec::new());
+        self.weights.push(Vec::new());
     }
 
-    /// Inserts an edge into the graph. Also inserts vertices if they were not present in the graph.
+    /// Inserts an edge without a weight into the graph, both vertices must be in the graph.
     pub fn add_edge(&mut self, from: usize, to: usize) {
-        if let Err(index) = self.adj[from].binary_search(&to) {
-            self.adj[from].insert(index, to);
-        }
-        if to == self.adj.len() {
-            println!("shit");
-        }
+        self.add_weighted_edge(from, to, f64::NAN);
+    }
+
+    /// Inserts an edge with weight `weight` into the graph, if the edge is already present the
+    /// largest of the two weights is kept. A weight of `NAN` means the weight is unknown.
+    pub fn add_weighted_edge(&mut self, from: usize, to: usize, weight: f64) {
+        assert!(
+            from < self.adj.len() && to < self.adj.len(),
+            "edge ({}, {}) ends at a vertex that is not in the graph",
+            from,
+            to
+        );
 
-        if let Err(index) = self.adj[to].binary_search(&from) {
-            self.adj[to].insert(index, from);
+        for (a, b) in [(from, to), (to, from)] {
+            match self.adj[a].binary_search(&b) {
+                Ok(index) => self.weights[a][index] = self.weights[a][index].max(weight),
+                Err(index) => {
+                    self.adj[a].insert(index, b);
+                    self.weights[a].insert(index, weight);
+                }
+            }
         }
     }
 
-    #[allow(dead_code)]
     /// Removes all vertices with one outgoing edge exhaustively
     pub fn reduce(&mut self) {
         // TODO fix indices
@@ -63,16 +167,19 @@ impl Graph {
                     set_vertices.insert(*vertex, set_vertices.len());
                 }
 
-                self.adj = good_vertices
+                let (adj, weights) = good_vertices
                     .iter()
                     .map(|index| {
                         self.adj[*index]
                             .iter()
-                            .filter(|j| set_vertices.contains_key(*j))
-                            .map(|j| *set_vertices.get(j).unwrap())
-                            .collect()
+                            .zip(&self.weights[*index])
+                            .filter(|(j, _)| set_vertices.contains_key(*j))
+                            .map(|(j, w)| (*set_vertices.get(j).unwrap(), *w))
+                            .unzip()
                     })
-                    .collect();
+                    .unzip();
+                self.adj = adj;
+                self.weights = weights;
             }
 
             if !reduced {
@@ -81,19 +188,175 @@ impl Graph {
         }
     }
 
+    /// Applies the steps of `cleaning` to the graph: first the ends of the edges are snapped, then the graph is
+    /// planarized, which also splits the edges that cross after snapping
+    pub fn clean(&mut self, cleaning: &Cleaning) -> CleaningReport {
+        let mut report = CleaningReport::default();
+        if cleaning.snap > 0. {
+            report.merged = self.snap(cleaning.snap);
+        }
+        if cleaning.planarize {
+            report.splits = self.planarize();
+        }
+        report
+    }
+
+    /// Merges every end of an edge, a vertex with at most one edge, with the vertices at most `tolerance` away
+    /// that it is not connected to, which closes the gaps left where digitised channels should meet. Vertices are
+    /// only merged by exact equality when the network is read, so without snapping, a channel ending one unit away
+    /// from another channel leaves the island between them open. Use a tolerance of at least `1.5` to also close
+    /// diagonal gaps of one unit.
+    ///
+    /// Only ends are snapped, as the vertices along the paths of the channels lie one unit apart. The ends are
+    /// clustered together with the vertices near them, also transitively, and every cluster is replaced by its
+    /// vertex with the most edges, the first one on ties, so an end snaps onto the channel it nearly touches. Nearby
+    /// vertices are found by hashing the vertices to the cells of a grid whose cells are `tolerance` wide. The
+    /// vertices that are kept keep their order, but not their indices. Returns the number of merged vertices.
+    pub fn snap(&mut self, tolerance: f64) -> usize {
+        if tolerance <= 0. || tolerance.is_nan() {
+            return 0;
+        }
+        let cell = |(x, y): (i32, i32)| {
+            (
+                (x as f64 / tolerance).floor() as i64,
+                (y as f64 / tolerance).floor() as i64,
+            )
+        };
+        let mut cells: FxHashMap<(i64, i64), Vec<usize>> = FxHashMap::default();
+        for (i, &vertex) in self.vertices.iter().enumerate() {
+            cells.entry(cell(vertex)).or_default().push(i);
+        }
+
+        let mut clusters = UnionFind::new(self.vertices.len());
+        for i in (0..self.vertices.len()).filter(|&i| self.adj[i].len() <= 1) {
+            let (x, y) = self.vertices[i];
+            let (column, row) = cell((x, y));
+            for neighbour in (-1..=1).flat_map(|dx| (-1..=1).map(move |dy| (column + dx, row + dy))) {
+                for &j in cells.get(&neighbour).into_iter().flatten() {
+                    let (dx, dy) = ((self.vertices[j].0 - x) as f64, (self.vertices[j].1 - y) as f64);
+                    if j != i && dx * dx + dy * dy <= tolerance * tolerance && self.adj[i].binary_search(&j).is_err() {
+                        clusters.union(i, j);
+                    }
+                }
+            }
+        }
+
+        // the vertex kept for every cluster, indexed by the root of the cluster
+        let mut kept = vec![usize::MAX; self.vertices.len()];
+        for i in 0..self.vertices.len() {
+            let r = clusters.find(i);
+            if kept[r] == usize::MAX || self.adj[i].len() > self.adj[kept[r]].len() {
+                kept[r] = i;
+            }
+        }
+        let merged = (0..self.vertices.len())
+            .filter(|&i| kept[clusters.find(i)] != i)
+            .count();
+        if merged == 0 {
+            return 0;
+        }
+
+        let mut graph = Graph::new();
+        let mut indices = vec![usize::MAX; self.vertices.len()];
+        for i in 0..self.vertices.len() {
+            if kept[clusters.find(i)] == i {
+                indices[i] = graph.vertices();
+                graph.add_vertex(self.vertices[i]);
+            }
+        }
+        for i in 0..self.vertices.len() {
+            let from = indices[kept[clusters.find(i)]];
+            for (&j, &weight) in self.adj[i].iter().zip(&self.weights[i]) {
+                let to = indices[kept[clusters.find(j)]];
+                // edges between merged vertices collapse
+                if j > i && from != to {
+                    graph.add_weighted_edge(from, to, weight);
+                }
+            }
+        }
+        *self = graph;
+        merged
+    }
+
+    /// Makes the graph planar, which [`Graph::polygons`] assumes: the edges are split where they cross or touch
+    /// another edge, or where collinear edges start to overlap, and the parts of the edges keep their weight. The
+    /// intersections are found with the sweep-line algorithm of Bentley and Ottmann, see
+    /// [`crate::geometry::interior_intersections`].
+    ///
+    /// The vertices have integer coordinates, so the intersections are rounded, and edges ending at the same rounded
+    /// point are joined there, like overlapping parts of edges. Rounding may move an edge across another one, so the
+    /// splitting is repeated until no edge is split, up to 8 times. The vertices of the graph keep their indices.
+    /// Returns the number of times an edge was split.
+    pub fn planarize(&mut self) -> usize {
+        let mut splits = 0;
+        for _ in 0..PLANARIZE_PASSES {
+            let split = self.split_intersections();
+            if split == 0 {
+                break;
+            }
+            splits += split;
+        }
+        splits
+    }
+
+    /// Splits the edges at their rounded intersections once, returns the number of splits
+    fn split_intersections(&mut self) -> usize {
+        let mut segments = Vec::new();
+        let mut weights = Vec::new();
+        for i in 0..self.adj.len() {
+            for (&j, &weight) in self.adj[i].iter().zip(&self.weights[i]) {
+                if j > i {
+                    segments.push((self.vertices[i], self.vertices[j]));
+                    weights.push(weight);
+                }
+            }
+        }
+        let intersections = interior_intersections(&segments);
+        if intersections.iter().all(Vec::is_empty) {
+            return 0;
+        }
+
+        let mut graph = Graph::new();
+        let mut indices = FxHashMap::default();
+        let mut vertex = |graph: &mut Graph, point: (i32, i32)| {
+            *indices.entry(point).or_insert_with(|| {
+                graph.add_vertex(point);
+                graph.vertices() - 1
+            })
+        };
+        for &point in &self.vertices {
+            vertex(&mut graph, point);
+        }
+        let mut splits = 0;
+        for ((&(from, to), points), &weight) in segments.iter().zip(&intersections).zip(&weights) {
+            let mut path = vec![from];
+            for &(x, y) in points {
+                let point = (x.round() as i32, y.round() as i32);
+                if point != from && point != to && path.last() != Some(&point) {
+                    path.push(point);
+                    splits += 1;
+                }
+            }
+            path.push(to);
+            for segment in path.windows(2) {
+                let (a, b) = (vertex(&mut graph, segment[0]), vertex(&mut graph, segment[1]));
+                graph.add_weighted_edge(a, b, weight);
+            }
+        }
+        *self = graph;
+        splits
+    }
+
     /// Returns the number of vertices in the graph
-    #[allow(dead_code)]
     pub fn vertices(&self) -> usize {
         self.vertices.len()
     }
 
     /// Returns the number of edges in the graph
-    #[allow(dead_code)]
     pub fn edges(&self) -> usize {
         self.adj.iter().fold(0, |acc, edges| acc + edges.len()) / 2
     }
 
-    #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
         self.vertices() == 0 && self.edges() == 0
     }
@@ -110,7 +373,7 @@ impl Graph {
                 let k = self.adj[i][j];
 
                 let to = &self.vertices[k];
-                dcel.add_edge_unchecked(from, to);
+                dcel.add_weighted_edge_unchecked(from, to, self.weights[i][j]);
             }
         }
 
@@ -119,9 +382,78 @@ impl Graph {
         dcel.make_polygons()
     }
 
-    #[allow(dead_code)]
-    pub fn to_ipe(&self) {
-        println!("<ipeselection pos=\"0 0\">");
+    /// Writes the graph as a network in the TTGA format, which [`crate::read_network`] reads back into the same
+    /// graph. Chains of vertices of degree two whose edges have the same weight are written as a single edge with a
+    /// path, the other vertices are the vertices of the network. Edges of unknown weight are written with the weight
+    /// `nan`, so they are removed when the network is read.
+    pub fn write_ttga<W: Write>(&self, out: &mut W) -> Result<()> {
+        let same = |a: f64, b: f64| a == b || (a.is_nan() && b.is_nan());
+        let mut junction: Vec<bool> = (0..self.vertices.len())
+            .map(|i| self.adj[i].len() != 2 || !same(self.weights[i][0], self.weights[i][1]))
+            .collect();
+
+        // the paths of the edges, as vertex indices, together with their weight
+        let mut edges: Vec<(Vec<usize>, f64)> = Vec::new();
+        let mut visited: Vec<Vec<bool>> = self.adj.iter().map(|adj| vec![false; adj.len()]).collect();
+        // after all the paths from junctions, the unvisited edges form cycles without a junction
+        let starts: Vec<usize> = (0..self.vertices.len())
+            .filter(|&i| junction[i])
+            .chain(0..self.vertices.len())
+            .collect();
+        for start in starts {
+            for first in 0..self.adj[start].len() {
+                if visited[start][first] {
+                    continue;
+                }
+                junction[start] = true;
+                let weight = self.weights[start][first];
+                let mut path = vec![start];
+                let (mut from, mut index) = (start, first);
+                loop {
+                    let to = self.adj[from][index];
+                    visited[from][index] = true;
+                    let back = self.adj[to]
+                        .binary_search(&from)
+                        .expect("the adjacency lists are symmetric");
+                    visited[to][back] = true;
+                    path.push(to);
+                    if junction[to] {
+                        break;
+                    }
+                    from = to;
+                    index = 1 - back;
+                }
+                edges.push((path, weight));
+            }
+        }
+
+        let mut ids = vec![usize::MAX; self.vertices.len()];
+        let junctions: Vec<usize> = (0..self.vertices.len()).filter(|&i| junction[i]).collect();
+        writeln!(out, "{}", junctions.len())?;
+        for (id, &i) in junctions.iter().enumerate() {
+            ids[i] = id;
+            writeln!(out, "{} {} {}", id, self.vertices[i].0, self.vertices[i].1)?;
+        }
+        writeln!(out, "{}", edges.len())?;
+        for (id, (path, weight)) in edges.iter().enumerate() {
+            let from = ids[path[0]];
+            let to = ids[path[path.len() - 1]];
+            if weight.is_nan() {
+                write!(out, "{} {} {} nan", id, from, to)?;
+            } else {
+                write!(out, "{} {} {} {:?}", id, from, to, weight)?;
+            }
+            for &vertex in path {
+                write!(out, " {} {}", self.vertices[vertex].0, self.vertices[vertex].1)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the edges of the graph as an Ipe selection
+    pub fn write_ipe<W: Write>(&self, out: &mut W) -> Result<()> {
+        writeln!(out, "<ipeselection pos=\"0 0\">")?;
         // for vertex in &self.vertices {
         //     println!("<use layer=\"alpha\" name=\"mark/disk(sx)\" pos=\"{} {}\" size=\"normal\" stroke=\"black\"/>",vertex.0, vertex.1);
         // }
@@ -135,13 +467,14 @@ impl Graph {
                 let k = self.adj[i][j];
 
                 let to = &self.vertices[k];
-                println!("<path layer=\"alpha\" stroke=\"black\">");
-                println!("{} {} m", from.0, from.1);
-                println!("{} {} l", to.0, to.1);
-                println!("</path>");
+                writeln!(out, "<path layer=\"alpha\" stroke=\"black\">")?;
+                writeln!(out, "{} {} m", from.0, from.1)?;
+                writeln!(out, "{} {} l", to.0, to.1)?;
+                writeln!(out, "</path>")?;
             }
         }
-        println!("</ipeselection>");
+        writeln!(out, "</ipeselection>")?;
+        Ok(())
     }
 }
 
@@ -205,4 +538,117 @@ mod tests {
 
         assert_eq!(graph.polygons().len(), 2);
     }
+
+    #[test]
+    fn planarize() {
+        // a square with a diagonal cross, and an edge along the bottom of the square
+        let mut graph = Graph::new();
+        for vertex in [(0, 0), (10, 0), (10, 10), (0, 10), (4, 0), (14, 0)] {
+            graph.add_vertex(vertex);
+        }
+        for (from, to, weight) in [
+            (0, 1, 1.),
+            (1, 2, 5.),
+            (2, 3, 5.),
+            (3, 0, 5.),
+            (0, 2, 2.),
+            (1, 3, 3.),
+            (4, 5, 9.),
+        ] {
+            graph.add_weighted_edge(from, to, weight);
+        }
+        assert_eq!(graph.planarize(), 4);
+        assert_eq!(graph.vertices(), 7);
+        assert_eq!(graph.edges(), 10);
+        let mut islands: Vec<_> = graph
+            .polygons()
+            .iter()
+            .map(|island| (island.area(), island.min_boundary_weight()))
+            .collect();
+        islands.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            islands,
+            vec![(25., Some(1.)), (25., Some(2.)), (25., Some(2.)), (25., Some(2.))]
+        );
+        assert_eq!(graph.planarize(), 0);
+    }
+
+    #[test]
+    fn planarize_rounds() {
+        // the crossing at (2.5, 2.5) is rounded to (3, 3)
+        let mut graph = Graph::new();
+        for vertex in [(0, 0), (5, 5), (0, 5), (5, 0)] {
+            graph.add_vertex(vertex);
+        }
+        graph.add_edge(0, 1);
+        graph.add_edge(2, 3);
+        graph.planarize();
+        assert_eq!(graph.vertices(), 5);
+        assert_eq!(graph.vertices[4], (3, 3));
+        assert_eq!(graph.edges(), 4);
+    }
+
+    #[test]
+    fn snap() {
+        // a square whose left side ends one unit short of the bottom, with a dangling edge ending near the corner
+        let mut graph = Graph::new();
+        for vertex in [(0, 0), (10, 0), (10, 10), (0, 10), (0, 1), (-1, -1), (-5, -5)] {
+            graph.add_vertex(vertex);
+        }
+        for (from, to, weight) in [(0, 1, 5.), (1, 2, 5.), (2, 3, 5.), (3, 4, 2.), (6, 5, 1.)] {
+            graph.add_weighted_edge(from, to, weight);
+        }
+        // the dangling edge forms a face without area
+        assert!(graph.polygons().iter().all(|island| island.area() == 0.));
+
+        let mut snapped = Graph::new();
+        snapped.add_vertex((0, 0));
+        snapped.add_vertex((0, 1));
+        snapped.add_edge(0, 1);
+        // the two ends of an edge are not merged
+        assert_eq!(snapped.snap(1.5), 0);
+
+        assert_eq!(graph.snap(1.), 1);
+        assert_eq!(graph.vertices(), 6);
+        assert_eq!(graph.vertices[4], (-1, -1));
+        let islands: Vec<_> = graph
+            .polygons()
+            .into_iter()
+            .filter(|island| island.area() > 0.)
+            .collect();
+        assert_eq!(islands.len(), 1);
+        assert_eq!(islands[0].area(), 100.);
+        assert_eq!(islands[0].min_boundary_weight(), Some(2.));
+
+        // the end at (-1, -1) is a diagonal unit away from the corner
+        assert_eq!(graph.snap(1.5), 1);
+        assert_eq!(graph.edges(), 5);
+    }
+
+    #[test]
+    fn boundary_weights() {
+        let mut graph = Graph::new();
+        graph.add_vertex((0, 0));
+        graph.add_vertex((1, 0));
+        graph.add_vertex((2, 0));
+        graph.add_vertex((0, 1));
+        graph.add_vertex((1, 1));
+        graph.add_vertex((2, 1));
+        graph.add_weighted_edge(0, 1, 5.);
+        graph.add_weighted_edge(1, 2, 5.);
+        graph.add_weighted_edge(3, 4, 5.);
+        graph.add_weighted_edge(4, 5, 4.);
+        graph.add_weighted_edge(0, 3, 5.);
+        graph.add_weighted_edge(1, 4, 2.);
+        graph.add_weighted_edge(1, 4, 3.);
+        graph.add_edge(2, 5);
+
+        let mut weights: Vec<_> = graph
+            .polygons()
+            .iter()
+            .map(|polygon| polygon.min_boundary_weight())
+            .collect();
+        weights.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(weights, vec![Some(3.), Some(3.)]);
+    }
 }
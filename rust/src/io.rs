@@ -1,89 +1,976 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Display;
 use std::fs::File;
-use std::io::Result;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
 
-use crate::graph::Graph;
+use crate::geometry::Polygon;
+use crate::graph::{Cleaning, Graph};
+use crate::raster::{count_tiff_pages, parse_grid, read_tiff_page, Grid};
+use crate::validation::{self, Report};
+use crate::wkt;
+use flate2::read::MultiGzDecoder;
 use fxhash::FxHashMap;
+use log::warn;
 
-pub fn read_network(delta: f64, path: &PathBuf) -> Result<Graph> {
-    let file = File::open(path)?;
+/// Returns the paths of all the network files (`*.txt`, and `*.csv` for WKT networks, possibly compressed, see
+/// [`open_file`]) in `dir`, sorted by file name so that the layer order does not depend on the order in which the
+/// file system lists the directory. CSV files without the header of a WKT network, such as seeds or results, are
+/// skipped.
+pub fn list_networks(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = file_name(&path);
+        if is_layer(&name) && (extension(&name) != "csv" || is_network_csv(File::open(&path)?, &name)?) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
 
-    let mut graph = Graph::new();
-    let mut reader = BufReader::new(file);
+/// A layer of the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    /// A file with a network or a grid, see [`read_islands`]
+    File(PathBuf),
+    /// Page `page` (counted from 0) of the TIFF stack at `stack`, to which the first page of `baseline` is added
+    Page {
+        stack: PathBuf,
+        page: usize,
+        baseline: Option<Baseline>,
+    },
+    /// The file named `member` in the tar archive at `archive`
+    Member { archive: PathBuf, member: String },
+}
 
-    let mut line = String::new();
-    reader.read_line(&mut line).unwrap();
+/// The first page of a TIFF that is added to the pages of a stack, decoded once for all of them by [`stack_inputs`]
+#[derive(Clone)]
+pub struct Baseline {
+    pub path: PathBuf,
+    pub grid: Arc<Grid>,
+}
 
-    // number of vertices
-    let n = line.trim().parse::<usize>().unwrap();
-    line.clear();
+impl fmt::Debug for Baseline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Baseline").field("path", &self.path).finish()
+    }
+}
 
-    // the actual vertices
-    let mut vertices = FxHashMap::default();
+/// Baselines are the same if they are read from the same file
+impl PartialEq for Baseline {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
 
-    for _ in 0..n {
-        reader.read_line(&mut line).unwrap();
-        let coords: Vec<_> = line.trim().split(' ').collect();
-        let x = coords[1].parse::<i32>().unwrap();
-        let y = coords[2].parse::<i32>().unwrap();
+impl Eq for Baseline {}
 
-        if !vertices.contains_key(&(x, y)) {
-            vertices.insert((x, y), vertices.len());
-            graph.add_vertex((x, y));
+impl Input {
+    /// Returns the file the layer is read from
+    pub fn path(&self) -> &Path {
+        match self {
+            Input::File(path) => path,
+            Input::Page { stack, .. } => stack,
+            Input::Member { archive, .. } => archive,
         }
-        line.clear();
     }
+}
 
-    reader.read_line(&mut line).unwrap();
-    // the number of edges
-    let m = line.trim().parse::<usize>().unwrap();
-    line.clear();
+impl From<PathBuf> for Input {
+    fn from(path: PathBuf) -> Self {
+        Input::File(path)
+    }
+}
 
-    for _ in 0..m {
-        reader.read_line(&mut line).unwrap();
-        // let data: Vec<_> = line.trim().split(' ').collect(); slow af
-        let mut data_iter = line.trim().split(' ');
-        data_iter.next();
-        data_iter.next();
-        data_iter.next();
-        let w = data_iter.next().unwrap();
-        let path: Vec<_> = data_iter.map(|s| s.parse::<i32>().unwrap()).collect();
-        let weight = if w == "nan" {
-            f64::NAN
-        } else {
-            w.parse::<f64>().unwrap()
-        };
-        if !weight.is_nan() && weight >= delta {
-            for v in (0..path.len() - 3).step_by(2) {
-                let x1 = path[v];
-                let y1 = path[v + 1];
-
-                let x2 = path[v + 2];
-                let y2 = path[v + 3];
-
-                let from = match vertices.get(&(x1, y1)) {
-                    Some(i) => *i,
-                    None => {
-                        vertices.insert((x1, y1), vertices.len());
-                        graph.add_vertex((x1, y1));
-                        vertices.len() - 1
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Input::File(path) => write!(f, "{}", path.display()),
+            Input::Page { stack, page, .. } => write!(f, "page {} of {}", page, stack.display()),
+            Input::Member { archive, member } => write!(f, "{} in {}", member, archive.display()),
+        }
+    }
+}
+
+/// Returns the network and grid files in `dir` as inputs, see [`list_networks`]. If `dir` is a tar archive, see
+/// [`list_archive`], its network and grid files are returned instead.
+pub fn list_inputs(dir: &Path) -> Result<Vec<Input>> {
+    if dir.is_file() && is_archive(&file_name(dir)) {
+        return list_archive(dir);
+    }
+    Ok(list_networks(dir)?.into_iter().map(Input::File).collect())
+}
+
+/// Returns the network and grid files in the tar archive at `archive` (`*.tar`, or compressed `*.tar.gz`, `*.tgz`,
+/// `*.tar.zst` or `*.tzst`), sorted by their path in the archive. The files may be compressed themselves. As in
+/// [`list_networks`], CSV files without the header of a WKT network are skipped.
+///
+/// The layers are read in one pass over the archive by an [`ArchiveReader`] as long as they are read in the order in
+/// which they are stored, which is the order of this list for archives written with sorted names (`tar --sort=name`).
+pub fn list_archive(archive: &Path) -> Result<Vec<Input>> {
+    let mut members = Vec::new();
+    let mut tar = tar::Archive::new(decoder(File::open(archive)?, &file_name(archive))?);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let member = entry.path()?.to_string_lossy().into_owned();
+        let name = member.rsplit('/').next().unwrap_or(&member).to_string();
+        if entry.header().entry_type().is_file()
+            && is_layer(&name)
+            && (extension(&name) != "csv" || is_network_csv(&mut entry, &name)?)
+        {
+            members.push(member);
+        }
+    }
+    members.sort();
+    Ok(members
+        .into_iter()
+        .map(|member| Input::Member {
+            archive: archive.to_path_buf(),
+            member,
+        })
+        .collect())
+}
+
+/// The path and contents of a layer file read from an archive
+type ArchiveMember = (String, Vec<u8>);
+
+/// Reads the layer files of a tar archive in the order in which they are stored. A thread decompresses the archive
+/// once and reads ahead by one layer, so reading the layers in their order decompresses the archive only once. Reading
+/// a layer stored before the previous one starts a new pass. The thread stops when the reader is dropped.
+pub struct ArchiveReader {
+    archive: PathBuf,
+    /// The path and contents of the layer files, from the thread reading the archive
+    receiver: Option<Receiver<Result<ArchiveMember>>>,
+    thread: Option<JoinHandle<()>>,
+    /// Whether no member was read since the pass started
+    fresh: bool,
+    /// How many passes were started
+    passes: usize,
+}
+
+impl ArchiveReader {
+    /// Returns a reader of the archive at `archive`, the first pass starts with the first read
+    pub fn open(archive: &Path) -> ArchiveReader {
+        ArchiveReader {
+            archive: archive.to_path_buf(),
+            receiver: None,
+            thread: None,
+            fresh: true,
+            passes: 0,
+        }
+    }
+
+    /// Returns a reader of the archive of the first of the `inputs` that is a member of an archive, if any
+    pub fn for_inputs(inputs: &[Input]) -> Option<ArchiveReader> {
+        inputs.iter().find_map(|input| match input {
+            Input::Member { archive, .. } => Some(ArchiveReader::open(archive)),
+            _ => None,
+        })
+    }
+
+    /// Returns the path of the archive
+    pub fn path(&self) -> &Path {
+        &self.archive
+    }
+
+    /// Stops the thread of the current pass, if any
+    fn stop(&mut self) {
+        // the thread stops sending as soon as its receiver is dropped
+        self.receiver = None;
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+
+    /// Starts a new pass over the archive
+    fn restart(&mut self) {
+        self.stop();
+        let (sender, receiver) = sync_channel(1);
+        let archive = self.archive.clone();
+        self.thread = Some(std::thread::spawn(move || {
+            let send = |sender: &SyncSender<_>| -> Result<()> {
+                let mut tar = tar::Archive::new(decoder(File::open(&archive)?, &file_name(&archive))?);
+                for entry in tar.entries()? {
+                    let mut entry = entry?;
+                    let member = entry.path()?.to_string_lossy().into_owned();
+                    if !entry.header().entry_type().is_file() || !is_layer(member.rsplit('/').next().unwrap_or(&member))
+                    {
+                        continue;
+                    }
+                    let mut contents = Vec::with_capacity(entry.size() as usize);
+                    entry.read_to_end(&mut contents)?;
+                    if sender.send(Ok((member, contents))).is_err() {
+                        break;
                     }
-                };
-                let to = match vertices.get(&(x2, y2)) {
-                    Some(i) => *i,
-                    None => {
-                        vertices.insert((x2, y2), vertices.len());
-                        graph.add_vertex((x2, y2));
-                        vertices.len() - 1
+                }
+                Ok(())
+            };
+            if let Err(error) = send(&sender) {
+                sender.send(Err(error)).ok();
+            }
+        }));
+        self.receiver = Some(receiver);
+        self.fresh = true;
+        self.passes += 1;
+    }
+
+    /// Returns the decompressed contents of the file `member` of the archive, continuing the current pass, or starting
+    /// a new pass if the member is not found after the previously read one
+    pub fn read(&mut self, member: &str) -> Result<Vec<u8>> {
+        let contents = self.read_compressed(member)?;
+        let mut bytes = Vec::new();
+        decoder(&contents[..], member)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Returns the contents of `member` as stored in the archive
+    fn read_compressed(&mut self, member: &str) -> Result<Vec<u8>> {
+        if self.receiver.is_none() {
+            self.restart();
+        }
+        // a pass that started before any member was read covers the whole archive
+        let mut restarted = self.fresh;
+        loop {
+            if let Some(receiver) = &self.receiver {
+                while let Ok(next) = receiver.recv() {
+                    self.fresh = false;
+                    let (path, contents) = next?;
+                    if path == member {
+                        return Ok(contents);
                     }
-                };
-                graph.add_edge(from, to);
+                }
+            }
+            if restarted {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("{} is not in {}", member, self.archive.display()),
+                ));
             }
+            self.restart();
+            restarted = true;
+        }
+    }
+}
+
+impl Drop for ArchiveReader {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Returns the decompressed contents of the file `member` of the tar archive at `archive`, read with `reader` if it
+/// reads that archive, or else in a pass of its own
+fn read_member(archive: &Path, member: &str, reader: Option<&mut ArchiveReader>) -> Result<Vec<u8>> {
+    match reader {
+        Some(reader) if reader.path() == archive => reader.read(member),
+        _ => ArchiveReader::open(archive).read(member),
+    }
+}
+
+/// Returns a reader decompressing `reader` if `name` ends with `.gz` or `.tgz` (gzip), or `.zst` or `.tzst` (zstd)
+fn decoder<'a, R: Read + 'a>(reader: R, name: &str) -> Result<Box<dyn Read + 'a>> {
+    Ok(if name.ends_with(".gz") || name.ends_with(".tgz") {
+        Box::new(MultiGzDecoder::new(reader))
+    } else if name.ends_with(".zst") || name.ends_with(".tzst") {
+        Box::new(zstd::stream::read::Decoder::new(reader)?)
+    } else {
+        Box::new(reader)
+    })
+}
+
+/// Opens the file at `path`, which is decompressed while it is read if its name ends with `.gz` (gzip) or `.zst`
+/// (zstd)
+pub fn open_file(path: &Path) -> Result<Box<dyn BufRead>> {
+    Ok(Box::new(BufReader::new(decoder(File::open(path)?, &file_name(path))?)))
+}
+
+/// Returns the decompressed contents of the file at `path`, see [`open_file`]
+pub fn read_file(path: &Path) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    decoder(File::open(path)?, &file_name(path))?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Returns the extension of a file name, ignoring the extension of the compression
+fn extension(name: &str) -> &str {
+    let name = name
+        .strip_suffix(".gz")
+        .or_else(|| name.strip_suffix(".zst"))
+        .unwrap_or(name);
+    match name.rfind('.') {
+        Some(dot) => &name[dot + 1..],
+        None => "",
+    }
+}
+
+/// Returns whether the file named `name` holds a network or a grid
+fn is_layer(name: &str) -> bool {
+    matches!(extension(name), "txt" | "csv")
+}
+
+/// Returns whether the CSV file named `name` with the contents `reader` starts with the header of a WKT network, see
+/// [`wkt::parse_network`]. An empty file is an empty network.
+fn is_network_csv<R: Read>(reader: R, name: &str) -> Result<bool> {
+    let mut reader = BufReader::new(decoder(reader, name)?);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        let header = String::from_utf8_lossy(&line);
+        if !header.trim().is_empty() {
+            return Ok(wkt::is_network_header(&header));
         }
         line.clear();
     }
+    Ok(true)
+}
 
-    // graph.reduce();
+fn is_archive(name: &str) -> bool {
+    [".tar", ".tar.gz", ".tgz", ".tar.zst", ".tzst"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+}
+
+/// Returns the pages `first` to `last` (the last page if `None`) of the TIFF stack at `stack` as inputs, so the
+/// first of these pages is layer 0. The first page of `baseline` is added to each page, it is decoded here once.
+pub fn stack_inputs(stack: &Path, baseline: Option<&Path>, first: usize, last: Option<usize>) -> Result<Vec<Input>> {
+    let pages = count_tiff_pages(stack)?;
+    let last = last.unwrap_or(pages - 1);
+    if first > last || last >= pages {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("cannot read frames {} to {} of a stack of {} pages", first, last, pages),
+        ));
+    }
+    let baseline = match baseline {
+        Some(path) => Some(Baseline {
+            path: path.to_path_buf(),
+            grid: Arc::new(read_tiff_page(path, 0)?),
+        }),
+        None => None,
+    };
+    Ok((first..=last)
+        .map(|page| Input::Page {
+            stack: stack.to_path_buf(),
+            page,
+            baseline: baseline.clone(),
+        })
+        .collect())
+}
+
+/// Reads the islands of a layer. A file is either a network, see [`read_graph`], of which the edges with weight at
+/// least `delta` are kept, or a grid of elevations. Grids are told apart by their header, which has more than one
+/// value. The islands of a grid, or a page of a TIFF stack, are the land above the water `level`. Networks are
+/// cleaned with `cleaning` before their islands are extracted, see [`Graph::clean`]. The members of an archive are
+/// read with `archive` if it reads their archive, see [`ArchiveReader::for_inputs`], or else in a pass of their own.
+pub fn read_islands(
+    input: &Input,
+    delta: f64,
+    level: f64,
+    cleaning: &Cleaning,
+    archive: Option<&mut ArchiveReader>,
+) -> Result<Vec<Polygon>> {
+    match input {
+        Input::File(path) => parse_islands(&file_name(path), &read_file(path)?, delta, level, cleaning),
+        Input::Member { archive: path, member } => {
+            parse_islands(member, &read_member(path, member, archive)?, delta, level, cleaning)
+        }
+        Input::Page { stack, page, baseline } => {
+            let mut grid = read_tiff_page(stack, *page)?;
+            if let Some(baseline) = baseline {
+                grid.add(&baseline.grid)?;
+            }
+            Ok(grid.islands(level))
+        }
+    }
+}
+
+/// Returns the islands of the decompressed contents `bytes` of the file named `name`, see [`read_islands`]
+fn parse_islands(name: &str, bytes: &[u8], delta: f64, level: f64, cleaning: &Cleaning) -> Result<Vec<Polygon>> {
+    if extension(name) != "csv" && starts_with_grid_header(bytes) {
+        Ok(parse_grid(bytes)?.islands(level))
+    } else {
+        let mut graph = parse_graph(name, bytes, delta)?;
+        let report = graph.clean(cleaning);
+        if !report.is_empty() {
+            warn!("Cleaned {}: {}", name, report);
+        }
+        Ok(graph.polygons())
+    }
+}
+
+/// Reads a network and keeps the edges with weight at least `delta`. A file with the extension `csv` holds the
+/// channels as WKT line strings, see [`crate::wkt::parse_network`], other files are in the TTGA format, see
+/// [`read_network`]. Compressed files are decompressed, see [`open_file`].
+pub fn read_graph(delta: f64, path: &Path) -> Result<Graph> {
+    parse_graph(&file_name(path), &read_file(path)?, delta)
+}
+
+fn parse_graph(name: &str, bytes: &[u8], delta: f64) -> Result<Graph> {
+    match extension(name) {
+        "csv" => wkt::parse_network(delta, std::str::from_utf8(bytes).map_err(invalid_data)?),
+        _ => parse_ttga(delta, bytes),
+    }
+}
+
+/// Checks the network of a layer for problems that would make reading it fail or give wrong islands, see
+/// [`validation::validate_ttga`] and [`validation::validate_wkt`]. Returns `None` for grids and the pages of stacks.
+/// The members of an archive are read as in [`read_islands`].
+pub fn validate_layer(input: &Input, archive: Option<&mut ArchiveReader>) -> Result<Option<Report>> {
+    let (name, bytes) = match input {
+        Input::File(path) => (file_name(path), read_file(path)?),
+        Input::Member { archive: path, member } => (member.clone(), read_member(path, member, archive)?),
+        Input::Page { .. } => return Ok(None),
+    };
+    Ok(match extension(&name) {
+        "csv" => Some(validation::validate_wkt(&bytes)),
+        _ if starts_with_grid_header(&bytes) => None,
+        _ => Some(validation::validate_ttga(&bytes)),
+    })
+}
+
+/// Writes `graph` to a network file in the TTGA format, see [`Graph::write_ttga`]
+pub fn write_network(graph: &Graph, path: &Path) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    graph.write_ttga(&mut out)?;
+    out.flush()
+}
+
+/// Returns whether the file at `path` is a grid rather than a network
+pub fn is_grid(path: &Path) -> Result<bool> {
+    if extension(&file_name(path)) == "csv" {
+        return Ok(false);
+    }
+    let mut line = String::new();
+    open_file(path)?.read_line(&mut line)?;
+    Ok(starts_with_grid_header(line.as_bytes()))
+}
+
+/// Returns whether the first line has more than one value, like the header of a grid
+fn starts_with_grid_header(bytes: &[u8]) -> bool {
+    let end = bytes.iter().position(|&byte| byte == b'\n').unwrap_or(bytes.len());
+    fields(&bytes[..end]).count() > 1
+}
+
+/// Constructs the error returned for malformed network files
+fn invalid_data<E: Display>(error: E) -> Error {
+    Error::new(ErrorKind::InvalidData, error.to_string())
+}
+
+/// Reads a network in the TTGA format, possibly compressed, see [`open_file`], and keeps the edges with weight at
+/// least `delta`. The file is parsed while it is read, see [`parse_ttga`], so it is never held in memory as a whole.
+/// Returns an error of kind `InvalidData` if the file is malformed.
+pub fn read_network(delta: f64, path: &Path) -> Result<Graph> {
+    parse_ttga(delta, open_file(path)?)
+}
+
+/// Lines of a network file read into a buffer that is reused for every line, and the number of the current line for
+/// error messages
+struct Lines<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    number: usize,
+}
+
+/// A line of a network file without the line break, together with its number
+struct Line<'a> {
+    bytes: &'a [u8],
+    number: usize,
+}
+
+impl<R: BufRead> Lines<R> {
+    /// Returns the next line, or an error at the end of the file
+    fn next(&mut self) -> Result<Line<'_>> {
+        self.buffer.clear();
+        if self.reader.read_until(b'\n', &mut self.buffer)? == 0 {
+            return Err(invalid_data(format!(
+                "unexpected end of file after line {}",
+                self.number
+            )));
+        }
+        self.number += 1;
+        let bytes = self.buffer.strip_suffix(b"\n").unwrap_or(&self.buffer);
+        Ok(Line {
+            bytes: bytes.strip_suffix(b"\r").unwrap_or(bytes),
+            number: self.number,
+        })
+    }
+}
+
+impl<'a> Line<'a> {
+    /// Returns the error for a malformed line
+    fn error(&self, what: &str) -> Error {
+        invalid_data(format!(
+            "line {}: malformed {} {:?}",
+            self.number,
+            what,
+            String::from_utf8_lossy(self.bytes).trim()
+        ))
+    }
+}
+
+/// Returns the fields of a line separated by spaces or tabs
+pub(crate) fn fields(line: &[u8]) -> impl Iterator<Item = &[u8]> {
+    line.split(|&byte| byte == b' ' || byte == b'\t')
+        .filter(|field| !field.is_empty())
+}
+
+/// Parses a decimal integer without going through a string
+pub(crate) fn parse_i32(field: &[u8]) -> Option<i32> {
+    let (negative, digits) = match field {
+        [b'-', digits @ ..] => (true, digits),
+        [b'+', digits @ ..] => (false, digits),
+        digits => (false, digits),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    let mut value: i64 = 0;
+    for &digit in digits {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + (digit - b'0') as i64;
+        if value > i32::MAX as i64 + 1 {
+            return None;
+        }
+    }
+    let value = if negative { -value } else { value };
+    i32::try_from(value).ok()
+}
+
+fn parse_count<R: BufRead>(lines: &mut Lines<R>, what: &str) -> Result<usize> {
+    let line = lines.next()?;
+    std::str::from_utf8(line.bytes)
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .ok_or_else(|| line.error(what))
+}
+
+/// Parses a network in the TTGA format from `reader`, e.g. the contents of a file as a byte slice, and keeps the edges
+/// with weight at least `delta`. The file is read one line at a time into a reused buffer, and the vertices and edges
+/// are inserted while scanning the bytes of the line, without copying the paths of the edges. Returns an error of kind
+/// `InvalidData`, naming the line, if the network is malformed.
+pub fn parse_ttga<R: BufRead>(delta: f64, reader: R) -> Result<Graph> {
+    let mut graph = Graph::new();
+    let mut lines = Lines {
+        reader,
+        buffer: Vec::new(),
+        number: 0,
+    };
+    let mut vertices: FxHashMap<(i32, i32), usize> = FxHashMap::default();
+    let mut vertex = |graph: &mut Graph, point: (i32, i32)| {
+        *vertices.entry(point).or_insert_with(|| {
+            graph.add_vertex(point);
+            graph.vertices() - 1
+        })
+    };
+
+    let n = parse_count(&mut lines, "vertex count")?;
+    for _ in 0..n {
+        let line = lines.next()?;
+        let mut fields = fields(line.bytes).skip(1);
+        let x = fields.next().and_then(parse_i32);
+        let y = fields.next().and_then(parse_i32);
+        match x.zip(y) {
+            Some(point) => vertex(&mut graph, point),
+            None => return Err(line.error("vertex")),
+        };
+    }
+
+    let m = parse_count(&mut lines, "edge count")?;
+    for _ in 0..m {
+        let line = lines.next()?;
+        let mut fields = fields(line.bytes).skip(3);
+        let weight = match fields.next() {
+            Some(b"nan") => f64::NAN,
+            Some(weight) => std::str::from_utf8(weight)
+                .ok()
+                .and_then(|weight| weight.parse::<f64>().ok())
+                .ok_or_else(|| line.error("edge weight"))?,
+            None => return Err(line.error("edge")),
+        };
+        let keep = !weight.is_nan() && weight >= delta;
+
+        // the vertex at the start of the current segment, inserted once the segment is complete
+        let mut previous: Option<((i32, i32), Option<usize>)> = None;
+        while let Some(x) = fields.next() {
+            let x = parse_i32(x).ok_or_else(|| line.error("edge path"))?;
+            let y = fields
+                .next()
+                .and_then(parse_i32)
+                .ok_or_else(|| line.error("edge path"))?;
+            if keep {
+                if let Some((point, index)) = previous {
+                    let from = index.unwrap_or_else(|| vertex(&mut graph, point));
+                    let to = vertex(&mut graph, (x, y));
+                    graph.add_weighted_edge(from, to, weight);
+                    previous = Some(((x, y), Some(to)));
+                    continue;
+                }
+            }
+            previous = Some(((x, y), None));
+        }
+    }
     Ok(graph)
 }
+
+/// The line-based parser used before [`parse_ttga`], the reference it is compared with in the tests. The benchmarks
+/// include the same file.
+#[cfg(test)]
+mod by_line;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An edge given by its end points and its weight as written
+    type Edge = ((i32, i32), (i32, i32), String);
+
+    /// Returns the edges of `graph` with their weights as pairs of coordinates, sorted
+    fn edges(graph: &Graph) -> Vec<Edge> {
+        let mut text = Vec::new();
+        graph.write_ttga(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let mut lines = text.lines();
+        let n: usize = lines.next().unwrap().parse().unwrap();
+        let mut lines = lines.skip(n + 1);
+        let mut edges = Vec::new();
+        for line in &mut lines {
+            let fields: Vec<_> = line.split(' ').collect();
+            let path: Vec<i32> = fields[4..].iter().map(|value| value.parse().unwrap()).collect();
+            for segment in path.chunks(2).collect::<Vec<_>>().windows(2) {
+                let (a, b) = ((segment[0][0], segment[0][1]), (segment[1][0], segment[1][1]));
+                edges.push((a.min(b), a.max(b), fields[3].to_string()));
+            }
+        }
+        edges.sort();
+        edges
+    }
+
+    fn round_trip(graph: &Graph, name: &str) -> Graph {
+        let path = std::env::temp_dir().join(format!("island-tracker-{}-{}.txt", name, std::process::id()));
+        write_network(graph, &path).unwrap();
+        let read = read_network(0., &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        read
+    }
+
+    #[test]
+    fn network_round_trip() {
+        // a square with a diagonal and a dangling path, the sides have different weights
+        let mut graph = Graph::new();
+        for vertex in [(0, 0), (5, 0), (10, 0), (10, 10), (0, 10), (-5, 10), (-10, 12)] {
+            graph.add_vertex(vertex);
+        }
+        for (from, to, weight) in [
+            (0, 1, 2.),
+            (1, 2, 2.),
+            (2, 3, 2.5),
+            (3, 4, 3.),
+            (4, 0, 3.),
+            (0, 3, 1.),
+            (4, 5, 7.),
+            (5, 6, 7.),
+        ] {
+            graph.add_weighted_edge(from, to, weight);
+        }
+        let mut text = Vec::new();
+        graph.write_ttga(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains(" 2.0 0 0 5 0 10 0\n"));
+        assert!(text.contains(" 7.0 0 10 -5 10 -10 12\n"));
+
+        let read = round_trip(&graph, "network");
+        assert_eq!(read.vertices(), graph.vertices());
+        assert_eq!(read.edges(), graph.edges());
+        assert_eq!(edges(&read), edges(&graph));
+        assert_eq!(read.polygons().len(), graph.polygons().len());
+    }
+
+    /// Parses `text` with both parsers, which should agree
+    fn parse_both(delta: f64, text: &str) -> Result<Graph> {
+        let path = std::env::temp_dir().join(format!("island-tracker-parse-{}-{}.txt", delta, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let by_line = by_line::read_network_by_line(delta, &path);
+        std::fs::remove_file(&path).unwrap();
+        let parsed = parse_ttga(delta, text.as_bytes());
+        match (&parsed, &by_line) {
+            (Ok(parsed), Ok(by_line)) => assert_eq!(parsed, by_line),
+            (Err(_), Err(_)) => {}
+            _ => panic!("the parsers disagree on {:?}: {:?} and {:?}", text, parsed, by_line),
+        }
+        parsed
+    }
+
+    #[test]
+    fn parsers_agree() {
+        let text = "3\r\n0 0 0\r\n1 10 0\r\n2 10 0\r\n5\r\n\
+                    0 0 1 150.5 0 0 5 -5 10 0\r\n\
+                    1 1 0 nan 10 0 0 10\r\n\
+                    2 1 1 200 10 0\r\n\
+                    3 0 1 20 0 0 0 10 10 10\r\n\
+                    4 1 1 1e3 10 0 10 0 10 10\r\n";
+        for delta in [0., 100., 500.] {
+            parse_both(delta, text).unwrap();
+        }
+        assert_eq!(parse_both(100., text).unwrap().edges(), 3);
+
+        let text = text.replace("\r\n", "\n");
+        assert_eq!(parse_both(0., &text).unwrap(), parse_both(0., text.trim_end()).unwrap());
+
+        for malformed in [
+            "",
+            "x\n",
+            "2\n0 0 0\n",
+            "1\n0 0\n0\n",
+            "1\n0 0 0\n1\n0 0 0\n",
+            "1\n0 0 0\n1\n0 0 0 5 0 0 1\n",
+            "1\n0 0 0\n1\n0 0 0 5 0 0 1 y\n",
+            "1\n0 0 0\n1\n0 0 0 w 0 0 1 1\n",
+            "1\n0 0 99999999999\n0\n",
+        ] {
+            assert!(parse_both(0., malformed).is_err(), "{:?} should be rejected", malformed);
+        }
+    }
+
+    #[test]
+    fn compressed_inputs() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let text = "4\n0 0 0\n1 10 0\n2 10 10\n3 0 10\n4\n0 0 1 5 0 0 10 0\n1 1 2 5 10 0 10 10\n\
+                    2 2 3 5 10 10 0 10\n3 3 0 5 0 10 0 0\n";
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(text.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::stream::encode_all(text.as_bytes(), 0).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("island-tracker-compressed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt.gz"), &gzip).unwrap();
+        std::fs::write(dir.join("b.txt.zst"), &zstd).unwrap();
+        std::fs::write(dir.join("c.txt"), text).unwrap();
+        std::fs::write(dir.join("notes.md"), "").unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, contents) in [
+            ("layers/b.txt.zst", &zstd[..]),
+            ("layers/a.txt.gz", &gzip[..]),
+            ("README", &[]),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, name, contents).unwrap();
+        }
+        let archive = dir.join("layers.tar");
+        std::fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+
+        let expected = parse_ttga(0., text.as_bytes()).unwrap();
+        let networks = list_networks(&dir).unwrap();
+        assert_eq!(
+            networks.iter().map(|path| file_name(path)).collect::<Vec<_>>(),
+            ["a.txt.gz", "b.txt.zst", "c.txt"]
+        );
+        for path in &networks {
+            assert_eq!(read_network(0., path).unwrap(), expected);
+        }
+
+        let members = list_inputs(&archive).unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(
+            members[0].to_string(),
+            format!("layers/a.txt.gz in {}", archive.display())
+        );
+        for member in &members {
+            assert_eq!(
+                read_islands(member, 0., 0., &Cleaning::default(), None).unwrap()[0].area(),
+                100.
+            );
+        }
+        let missing = Input::Member {
+            archive,
+            member: "layers/c.txt".to_string(),
+        };
+        let error = read_islands(&missing, 0., 0., &Cleaning::default(), None).err();
+        assert_eq!(error.map(|error| error.kind()), Some(ErrorKind::NotFound));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compressed_archive_in_one_pass() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for layer in 0..20 {
+            let size = 10 + layer;
+            let text = format!(
+                "4\n0 0 0\n1 {s} 0\n2 {s} {s}\n3 0 {s}\n4\n0 0 1 5 0 0 {s} 0\n1 1 2 5 {s} 0 {s} {s}\n\
+                 2 2 3 5 {s} {s} 0 {s}\n3 3 0 5 0 {s} 0 0\n",
+                s = size
+            );
+            let mut header = tar::Header::new_gnu();
+            header.set_size(text.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("layers/{:03}.txt", layer), text.as_bytes())
+                .unwrap();
+        }
+        let dir = std::env::temp_dir().join(format!("island-tracker-pass-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let archive = dir.join("layers.tar.zst");
+        std::fs::write(
+            &archive,
+            zstd::stream::encode_all(&builder.into_inner().unwrap()[..], 0).unwrap(),
+        )
+        .unwrap();
+
+        let inputs = list_inputs(&archive).unwrap();
+        assert_eq!(inputs.len(), 20);
+        let mut reader = ArchiveReader::for_inputs(&inputs).unwrap();
+        for (layer, input) in inputs.iter().enumerate() {
+            let size = 10. + layer as f64;
+            let islands = read_islands(input, 0., 0., &Cleaning::default(), Some(&mut reader)).unwrap();
+            assert_eq!(islands[0].area(), size * size);
+        }
+        assert_eq!(reader.passes, 1);
+        // an earlier layer starts a new pass
+        assert!(reader.read("layers/003.txt").is_ok());
+        assert_eq!(reader.passes, 2);
+        assert_eq!(
+            reader.read("layers/020.txt").err().map(|error| error.kind()),
+            Some(ErrorKind::NotFound)
+        );
+        // dropping the reader stops the thread of the pass
+        assert!(reader.thread.is_some());
+        drop(reader);
+
+        // without a reader, every layer is read in a pass of its own
+        let size = 10. + 19.;
+        assert_eq!(
+            read_islands(&inputs[19], 0., 0., &Cleaning::default(), None).unwrap()[0].area(),
+            size * size
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stack_with_baseline() {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        let dir = std::env::temp_dir().join(format!("island-tracker-baseline-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (stack, baseline) = (dir.join("stack.tiff"), dir.join("baseline.tiff"));
+        let mut encoder = TiffEncoder::new(File::create(&stack).unwrap()).unwrap();
+        let mut frame = vec![0f32; 5 * 5];
+        encoder.write_image::<colortype::Gray32Float>(5, 5, &frame).unwrap();
+        frame[5 + 1] = 1.;
+        encoder.write_image::<colortype::Gray32Float>(5, 5, &frame).unwrap();
+        drop(encoder);
+        let mut trend = vec![0f32; 5 * 5];
+        trend[3 * 5 + 3] = 1.;
+        TiffEncoder::new(File::create(&baseline).unwrap())
+            .unwrap()
+            .write_image::<colortype::Gray32Float>(5, 5, &trend)
+            .unwrap();
+
+        let inputs = stack_inputs(&stack, Some(&baseline), 0, None).unwrap();
+        let counts: Vec<_> = inputs
+            .iter()
+            .map(|input| read_islands(input, 0., 0.5, &Cleaning::default(), None).unwrap().len())
+            .collect();
+        assert_eq!(counts, [1, 2]);
+        // the baseline is decoded once
+        let grids: Vec<_> = inputs
+            .iter()
+            .map(|input| match input {
+                Input::Page {
+                    baseline: Some(baseline),
+                    ..
+                } => Arc::clone(&baseline.grid),
+                _ => panic!("{} has no baseline", input),
+            })
+            .collect();
+        assert!(Arc::ptr_eq(&grids[0], &grids[1]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn csv_layers() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let network = "id,WKT\n1,\"LINESTRING (0 0, 10 0, 10 10, 0 10, 0 0)\"\n";
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(network.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let files: [(&str, &[u8]); 5] = [
+            ("a.csv", network.as_bytes()),
+            ("b.csv.gz", &gzip),
+            ("counts.csv", b"layer,islands\n0,1\n"),
+            ("seeds.csv", b"\n\nname,x,y\nbar,5,5\n"),
+            ("c.txt", b"0\n0\n"),
+        ];
+
+        let dir = std::env::temp_dir().join(format!("island-tracker-csv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, name, contents).unwrap();
+        }
+        let networks = list_networks(&dir).unwrap();
+        assert_eq!(
+            networks.iter().map(|path| file_name(path)).collect::<Vec<_>>(),
+            ["a.csv", "b.csv.gz", "c.txt"]
+        );
+        for path in &networks[..2] {
+            assert_eq!(
+                read_islands(&Input::File(path.clone()), 0., 0., &Cleaning::default(), None)
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+
+        let archive = dir.join("layers.tar");
+        std::fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+        let members: Vec<_> = list_archive(&archive)
+            .unwrap()
+            .iter()
+            .map(|input| input.to_string())
+            .collect();
+        assert_eq!(
+            members,
+            ["a.csv", "b.csv.gz", "c.txt"].map(|name| format!("{} in {}", name, archive.display()))
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cycle_round_trip() {
+        let mut graph = Graph::new();
+        for vertex in [(0, 0), (10, 0), (10, 10), (0, 10)] {
+            graph.add_vertex(vertex);
+        }
+        for i in 0..4 {
+            graph.add_weighted_edge(i, (i + 1) % 4, 4.);
+        }
+        let read = round_trip(&graph, "cycle");
+        assert_eq!(edges(&read), edges(&graph));
+        assert_eq!(read.polygons()[0].area(), 100.);
+    }
+}
@@ -0,0 +1,107 @@
+// The line-based parser of TTGA networks used before `io::parse_ttga`, kept as the reference its output is compared
+// with. The library tests and the benchmarks both include this file, so it only uses the `Graph` of the including
+// crate.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result};
+use std::path::Path;
+
+use fxhash::FxHashMap;
+
+use super::Graph;
+
+fn invalid_data<E: ToString>(error: E) -> Error {
+    Error::new(ErrorKind::InvalidData, error.to_string())
+}
+
+/// Reads a network like `io::read_network`, one line at a time
+pub fn read_network_by_line(delta: f64, path: &Path) -> Result<Graph> {
+    let file = File::open(path)?;
+
+    let mut graph = Graph::new();
+    let mut reader = BufReader::new(file);
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    // number of vertices
+    let n = line.trim().parse::<usize>().map_err(invalid_data)?;
+    line.clear();
+
+    // the actual vertices
+    let mut vertices = FxHashMap::default();
+
+    for _ in 0..n {
+        reader.read_line(&mut line)?;
+        let coords: Vec<_> = line.trim().split(' ').collect();
+        if coords.len() < 3 {
+            return Err(invalid_data(format!("malformed vertex {:?}", line.trim())));
+        }
+        let x = coords[1].parse::<i32>().map_err(invalid_data)?;
+        let y = coords[2].parse::<i32>().map_err(invalid_data)?;
+
+        if !vertices.contains_key(&(x, y)) {
+            vertices.insert((x, y), vertices.len());
+            graph.add_vertex((x, y));
+        }
+        line.clear();
+    }
+
+    reader.read_line(&mut line)?;
+    // the number of edges
+    let m = line.trim().parse::<usize>().map_err(invalid_data)?;
+    line.clear();
+
+    for _ in 0..m {
+        reader.read_line(&mut line)?;
+        let mut data_iter = line.trim().split(' ');
+        data_iter.next();
+        data_iter.next();
+        data_iter.next();
+        let w = data_iter
+            .next()
+            .ok_or_else(|| invalid_data(format!("malformed edge {:?}", line.trim())))?;
+        let path = data_iter
+            .map(|s| s.parse::<i32>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(invalid_data)?;
+        if path.len() % 2 != 0 {
+            return Err(invalid_data(format!("malformed edge path {:?}", line.trim())));
+        }
+        let weight = if w == "nan" {
+            f64::NAN
+        } else {
+            w.parse::<f64>().map_err(invalid_data)?
+        };
+        if !weight.is_nan() && weight >= delta {
+            for v in (0..path.len().saturating_sub(3)).step_by(2) {
+                let x1 = path[v];
+                let y1 = path[v + 1];
+
+                let x2 = path[v + 2];
+                let y2 = path[v + 3];
+
+                let from = match vertices.get(&(x1, y1)) {
+                    Some(i) => *i,
+                    None => {
+                        vertices.insert((x1, y1), vertices.len());
+                        graph.add_vertex((x1, y1));
+                        vertices.len() - 1
+                    }
+                };
+                let to = match vertices.get(&(x2, y2)) {
+                    Some(i) => *i,
+                    None => {
+                        vertices.insert((x2, y2), vertices.len());
+                        graph.add_vertex((x2, y2));
+                        vertices.len() - 1
+                    }
+                };
+                graph.add_weighted_edge(from, to, weight);
+            }
+        }
+        line.clear();
+    }
+
+    Ok(graph)
+}
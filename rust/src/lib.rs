@@ -0,0 +1,64 @@
+//! Tracking islands in braided river networks over time.
+//!
+//! A layer of the input is a network of channels, read with [`read_network`] into a [`Graph`]. The faces of the
+//! planar graph, computed with a [`DCEL`], are the islands of the layer, given as [`Polygon`]s:
+//!
+//! ```
+//! use island_tracker::Graph;
+//!
+//! let mut graph = Graph::new();
+//! for vertex in [(0, 0), (10, 0), (10, 10), (0, 10)] {
+//!     graph.add_vertex(vertex);
+//! }
+//! for i in 0..4 {
+//!     graph.add_weighted_edge(i, (i + 1) % 4, 1.);
+//! }
+//! let islands = graph.polygons();
+//! assert_eq!(islands.len(), 1);
+//! assert_eq!(islands[0].area(), 100.);
+//! ```
+//!
+//! The islands of a seed are tracked over the layers with [`compute_reeb_graph`], or [`track_seeds`] for many seeds at
+//! once. The result holds a [`ReebGraph`] for every island of the seed, whose critical points are the islands matched
+//! in every layer:
+//!
+//! ```no_run
+//! use island_tracker::{compute_reeb_graph, list_inputs, Region, TrackingOptions};
+//! use rand::rngs::StdRng;
+//! use rand::SeedableRng;
+//!
+//! let inputs = list_inputs("networks".as_ref())?;
+//! let times: Vec<usize> = (0..inputs.len()).collect();
+//! let mut rng = StdRng::seed_from_u64(0);
+//! let options = TrackingOptions::default();
+//! for tracked in compute_reeb_graph(&inputs, &Region::Point((120., 80.)), 0, &times, &options, &mut rng)? {
+//!     tracked.reeb.write_ipe(&mut std::io::stdout())?;
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod assignment;
+mod csv;
+pub mod geojson;
+pub mod geometry;
+pub mod graph;
+pub mod io;
+pub mod lineage;
+pub mod raster;
+pub mod reeb_graph;
+pub mod seeds;
+pub mod statistics;
+pub mod tracking;
+pub mod validation;
+pub mod wkt;
+
+pub use geometry::{Polygon, DCEL};
+pub use graph::{Cleaning, CleaningReport, Graph};
+pub use io::{list_inputs, list_networks, read_graph, read_islands, read_network, stack_inputs, write_network, Input};
+pub use raster::{read_grid, Grid};
+pub use reeb_graph::{CriticalKind, CriticalPoint, ReebGraph};
+pub use seeds::{Choice, Region, Seed};
+pub use tracking::{
+    compute_reeb_graph, schedule, track_seeds, Direction, Matching, MatchingKind, Method, Tracked, TrackedSeeds,
+    TrackingError, TrackingOptions,
+};
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+
+use serde_json::{json, Number, Value};
+
+use crate::csv::field;
+use crate::reeb_graph::ReebGraph;
+
+/// The state of a tracked island in a single layer, aggregated over all its fragments
+#[derive(Debug, PartialEq)]
+pub struct LineageRow {
+    pub layer: i32,
+    /// Number of islands in the layer that belong to the tracked island
+    pub fragments: usize,
+    /// Total area of all the fragments
+    pub area: f64,
+    /// Area-weighted centroid of all the fragments
+    pub centroid: (f64, f64),
+    /// Distance the centroid moved per layer since the previous row, `None` for the first row
+    pub speed: Option<f64>,
+    /// Angle in radians in `(-pi, pi]` of the direction the centroid moved since the previous row
+    pub direction: Option<f64>,
+}
+
+/// Extracts the time series of the island tracked in `reeb`, with one row per layer in increasing order.
+/// Critical points without shape descriptors are ignored.
+pub fn lineage(reeb: &ReebGraph) -> Vec<LineageRow> {
+    // layer -> (fragments, area, area-weighted sum of the centroids)
+    let mut layers: BTreeMap<i32, (usize, f64, (f64, f64))> = BTreeMap::new();
+    for (point, layer) in reeb.points() {
+        if let Some(descriptors) = reeb.descriptors(point) {
+            let entry = layers.entry(layer).or_insert((0, 0., (0., 0.)));
+            entry.0 += 1;
+            entry.1 += descriptors.area;
+            (entry.2).0 += descriptors.area * descriptors.centroid.0;
+            (entry.2).1 += descriptors.area * descriptors.centroid.1;
+        }
+    }
+
+    let mut rows: Vec<LineageRow> = Vec::new();
+    for (layer, (fragments, area, weighted)) in layers {
+        let centroid = if area == 0. {
+            (f64::NAN, f64::NAN)
+        } else {
+            (weighted.0 / area, weighted.1 / area)
+        };
+
+        let (speed, direction) = match rows.last() {
+            Some(previous) => {
+                let dx = centroid.0 - previous.centroid.0;
+                let dy = centroid.1 - previous.centroid.1;
+                let elapsed = (layer - previous.layer) as f64;
+                (Some(dx.hypot(dy) / elapsed), Some(dy.atan2(dx)))
+            }
+            None => (None, None),
+        };
+
+        rows.push(LineageRow {
+            layer,
+            fragments,
+            area,
+            centroid,
+            speed,
+            direction,
+        });
+    }
+    rows
+}
+
+pub fn write_csv<W: Write>(rows: &[LineageRow], out: &mut W) -> Result<()> {
+    writeln!(out, "layer,fragments,area,centroid_x,centroid_y,speed,direction")?;
+    for row in rows {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{}",
+            row.layer,
+            row.fragments,
+            field(row.area),
+            field(row.centroid.0),
+            field(row.centroid.1),
+            field(row.speed.unwrap_or(f64::NAN)),
+            field(row.direction.unwrap_or(f64::NAN)),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the rows as a JSON array of objects, unknown values are written as `null`
+pub fn write_json<W: Write>(rows: &[LineageRow], out: &mut W) -> Result<()> {
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            json!({
+                "layer": row.layer,
+                "fragments": row.fragments,
+                "area": number(row.area),
+                "centroid": [number(row.centroid.0), number(row.centroid.1)],
+                "speed": row.speed.map_or(Value::Null, number),
+                "direction": row.direction.map_or(Value::Null, number),
+            })
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &rows)?;
+    writeln!(out)
+}
+
+/// Returns a JSON number, JSON has no representation for `NAN` and infinities so these become `null`
+fn number(value: f64) -> Value {
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Polygon;
+    use crate::reeb_graph::CriticalPoint;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn square(x: f64, y: f64, size: f64) -> Polygon {
+        Polygon::new(vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)])
+    }
+
+    #[test]
+    fn split_island() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let root = CriticalPoint::new(0);
+        let mut reeb = ReebGraph::new(&root, 1., 4);
+        reeb.set_descriptors(&root, square(0., 0., 2.).descriptors(&mut rng).unwrap());
+        for (id, x) in [(1, 3.), (2, 9.)] {
+            let point = CriticalPoint::new(id);
+            reeb.add_point(4, &root, &point, x);
+            reeb.set_descriptors(&point, square(x - 1., 3., 2.).descriptors(&mut rng).unwrap());
+        }
+
+        let mut rows = lineage(&reeb);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].layer, 4);
+        assert_eq!(rows[0].centroid, (1., 1.));
+        assert_eq!(rows[0].speed, None);
+        assert_eq!(rows[1].layer, 5);
+        assert_eq!(rows[1].fragments, 2);
+        assert_eq!(rows[1].area, 8.);
+        assert_eq!(rows[1].centroid, (6., 4.));
+        assert_eq!(rows[1].speed, Some(5f64.hypot(3.)));
+
+        let mut out = Vec::new();
+        write_json(&rows, &mut out).unwrap();
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json[0],
+            json!({"layer": 4, "fragments": 1, "area": 4., "centroid": [1., 1.], "speed": null, "direction": null})
+        );
+        assert_eq!(json[1]["speed"], json!(5f64.hypot(3.)));
+
+        let unknown = LineageRow {
+            area: f64::NAN,
+            ..rows.remove(0)
+        };
+        let mut out = Vec::new();
+        write_json(&[unknown], &mut out).unwrap();
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["area"], Value::Null);
+    }
+}
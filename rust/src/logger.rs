@@ -0,0 +1,65 @@
+use std::io::Write;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Writes log messages to standard error, so standard output only carries the results
+struct Logger {
+    level: LevelFilter,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let level = match record.level() {
+                Level::Error => "error",
+                Level::Warn => "warning",
+                Level::Info => "info",
+                Level::Debug => "debug",
+                Level::Trace => "trace",
+            };
+            let _ = writeln!(std::io::stderr(), "{}: {}", level, record.args());
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// Returns the most detailed level that is logged: only errors when `quiet`, otherwise warnings and one more level
+/// per `verbose` flag
+pub fn level(verbose: u64, quiet: bool) -> LevelFilter {
+    if quiet {
+        return LevelFilter::Error;
+    }
+    match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Installs the logger, messages above `level` are discarded. Only the first call has an effect.
+pub fn init(level: LevelFilter) {
+    if log::set_logger(Box::leak(Box::new(Logger { level }))).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        assert_eq!(level(0, false), LevelFilter::Warn);
+        assert_eq!(level(2, false), LevelFilter::Debug);
+        assert_eq!(level(5, false), LevelFilter::Trace);
+        assert_eq!(level(3, true), LevelFilter::Error);
+    }
+}
@@ -1,224 +1,1050 @@
-mod geometry;
-mod graph;
-mod io;
-mod reeb_graph;
-
-use crate::reeb_graph::CriticalPoint;
-use fxhash::FxHashSet;
-use reeb_graph::ReebGraph;
-use std::collections::VecDeque;
-use std::{fs::DirEntry, io::Error, path::PathBuf};
+mod config;
+mod logger;
+mod manifest;
+
+use island_tracker::geojson::{self, Clock, Encoding, IslandFeature};
+use island_tracker::geometry::{simplify_islands, Simplification};
+use island_tracker::io::ArchiveReader;
+use island_tracker::statistics::{self, IslandPersistence};
+use island_tracker::validation::{self, Report};
+use island_tracker::{
+    compute_reeb_graph, io, lineage, schedule, seeds, track_seeds, Cleaning, Direction, Input, Matching, MatchingKind,
+    Method, Polygon, Region, Tracked, TrackingOptions,
+};
+use log::{error, info, warn};
+use manifest::Manifest;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
 use structopt::StructOpt;
 
+use geo_svg::{Color, ToSvg};
+
 #[derive(Debug, StructOpt)]
 struct Opt {
+    /// Seed for all randomized geometry, a random seed is chosen (and written to the manifest) if omitted
+    #[structopt(long, global = true)]
+    seed: Option<u64>,
+
+    /// Path of the run manifest
+    #[structopt(long, parse(from_os_str), default_value = "manifest.toml", global = true)]
+    manifest: PathBuf,
+
+    /// TOML file with the options of the run, options given on the command line take precedence
+    #[structopt(long, parse(from_os_str), global = true)]
+    config: Option<PathBuf>,
+
+    /// Logs more details to standard error: -v for the progress per layer, -vv for debugging
+    #[structopt(short, long, parse(from_occurrences), global = true)]
+    verbose: u64,
+
+    /// Only logs errors
+    #[structopt(short, long, global = true)]
+    quiet: bool,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Counts the number of islands in every compared layer (formats: text, csv, json)
+    Count {
+        #[structopt(flatten)]
+        input: InputOpt,
+        #[structopt(flatten)]
+        output: OutputOpt,
+    },
+    /// Tracks the island of a seed point, box or seed file over time
+    /// (formats: ipe for the Reeb graph, csv or json for the time series, geojson, wkt or wkb for the tracked islands)
+    Track {
+        #[structopt(flatten)]
+        input: InputOpt,
+        #[structopt(flatten)]
+        output: OutputOpt,
+        #[structopt(flatten)]
+        track: TrackOpt,
+        #[structopt(flatten)]
+        islands: IslandsOpt,
+    },
+    /// Computes shape statistics of every island and every compared layer (formats: csv)
+    Stats {
+        #[structopt(flatten)]
+        input: InputOpt,
+        #[structopt(flatten)]
+        output: OutputOpt,
+        /// CSV file with one row per layer (the islands are written to --output)
+        #[structopt(long, parse(from_os_str))]
+        layers_output: Option<PathBuf>,
+    },
+    /// Draws the islands of a single layer (formats: ipe, svg)
+    Render {
+        #[structopt(flatten)]
+        source: SourceOpt,
+        /// Layer to draw
+        #[structopt(short, long)]
+        layer: usize,
+        #[structopt(flatten)]
+        simplify: SimplifyOpt,
+        #[structopt(flatten)]
+        output: OutputOpt,
+    },
+    /// Reads a single network, removes the edges lighter than delta and the dangling paths (formats: ipe, ttga)
+    Preprocess {
+        /// Network file in the TTGA format, or a CSV file of WKT line strings
+        #[structopt(parse(from_os_str))]
+        network: PathBuf,
+        #[structopt(short, long, default_value = "1e2")]
+        delta: f64,
+        #[structopt(flatten)]
+        cleaning: CleaningOpt,
+        #[structopt(flatten)]
+        output: OutputOpt,
+    },
+    /// Lists the persistence of every island, the smallest weight of the channels around it (formats: csv, json)
+    Persistence {
+        #[structopt(flatten)]
+        input: InputOpt,
+        #[structopt(flatten)]
+        output: OutputOpt,
+    },
+    /// Exports the islands of every compared layer (formats: geojson, wkt, wkb)
+    Export {
+        #[structopt(flatten)]
+        input: InputOpt,
+        #[structopt(flatten)]
+        output: OutputOpt,
+        #[structopt(flatten)]
+        islands: IslandsOpt,
+    },
+    /// Checks every network for problems and reports them per file, fails if any network has problems
+    /// (formats: text, csv, json)
+    Validate {
+        /// Directory, or tar archive, of the networks
+        #[structopt(short, long, parse(from_os_str), default_value = "networks/")]
+        input_dir: PathBuf,
+        #[structopt(flatten)]
+        output: OutputOpt,
+    },
+}
+
+/// Where the layers are read from and how their islands are extracted
+#[derive(Debug, StructOpt)]
+struct SourceOpt {
+    #[structopt(short, long, parse(from_os_str), default_value = "networks/")]
+    input_dir: PathBuf,
+
+    /// Multi-page TIFF of which every page is a grid layer, instead of the files in the input directory
+    #[structopt(long, parse(from_os_str))]
+    stack: Option<PathBuf>,
+
+    /// TIFF of which the first page is added to every page of the stack
+    #[structopt(long, parse(from_os_str), requires = "stack")]
+    baseline: Option<PathBuf>,
+
+    /// First page of the stack to read, which becomes layer 0 (default: the first page)
+    #[structopt(long, requires = "stack")]
+    first_frame: Option<usize>,
+
+    /// Last page of the stack to read (default: the last page)
+    #[structopt(long, requires = "stack")]
+    last_frame: Option<usize>,
+
     #[structopt(short, long, default_value = "1e2")]
     delta: f64,
 
+    /// Water level above which the cells of raster layers are land
+    #[structopt(long, default_value = "0")]
+    level: f64,
+
+    #[structopt(flatten)]
+    cleaning: CleaningOpt,
+}
+
+/// How the networks are cleaned before their islands are extracted
+#[derive(Debug, StructOpt)]
+struct CleaningOpt {
+    /// Distance within which the ends of channels are merged with other vertices, closing small gaps (0 to keep them)
+    #[structopt(long, default_value = "0", validator = non_negative)]
+    snap: f64,
+
+    /// Splits the channels where they cross, for networks whose digitised channels overlap
+    #[structopt(long)]
+    planarize: bool,
+}
+
+/// The layers to read and which of them to compare
+#[derive(Debug, StructOpt)]
+struct InputOpt {
+    #[structopt(flatten)]
+    source: SourceOpt,
+
     #[structopt(short, long, default_value = "0")]
     start_time: usize,
 
-    #[structopt(short, long, parse(from_os_str), default_value = "networks/")]
-    input_dir: PathBuf,
+    /// Last layer to consider (default: the last layer)
+    #[structopt(long)]
+    end_time: Option<usize>,
+
+    /// Number of layers between compared layers
+    #[structopt(long, default_value = "1")]
+    stride: usize,
+
+    /// Comma separated list of layers to compare, overrides the end time and stride
+    #[structopt(long, use_delimiter = true)]
+    layers: Vec<usize>,
+}
+
+/// Where and how the results are written
+#[derive(Debug, StructOpt)]
+struct OutputOpt {
+    /// File to write the results to (default: standard output)
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
 
+    /// Format of the results, the supported formats depend on the command
+    #[structopt(short, long)]
+    format: Option<Format>,
+}
+
+#[derive(Debug, StructOpt)]
+struct TrackOpt {
     #[structopt(short, long, default_value = "0.")]
     x: f64,
 
     #[structopt(short, long, default_value = "0.")]
     y: f64,
 
+    /// Box xmin,ymin,xmax,ymax in the island to track, instead of the point given by -x and -y
+    #[structopt(name = "box", long = "box", use_delimiter = true, number_of_values = 4)]
+    bounding_box: Vec<f64>,
+
+    /// Largest distance to the nearest island used when no island contains the seed point or overlaps the seed box
+    #[structopt(long, default_value = "0", validator = non_negative)]
+    tolerance: f64,
+
+    /// CSV or GeoJSON file of named seed points to track instead of the point given by -x and -y
+    #[structopt(long, parse(from_os_str))]
+    seeds: Option<PathBuf>,
+
+    /// Point of an island used to match it: centroid (polygonal centroid) or disk (center of the smallest enclosing
+    /// disk)
     #[structopt(short, long, default_value = "centroid")]
-    algorithm: String,
+    method: Method,
+
+    /// Direction in time in which the island is tracked: forward, backward or both
+    #[structopt(long, default_value = "forward")]
+    direction: Direction,
+
+    /// How islands of compared layers are matched: containment (of each other's centroids) or optimal (global
+    /// assignment)
+    #[structopt(long, default_value = "containment")]
+    matching: MatchingKind,
+
+    /// Cost above which the optimal matching leaves islands unmatched
+    #[structopt(long, default_value = "1.5", validator = positive)]
+    max_cost: f64,
+
+    /// Extra cost of attaching an island as part of a merge or split in the optimal matching
+    #[structopt(long, default_value = "0.25", validator = non_negative)]
+    split_penalty: f64,
+
+    /// Number of consecutive layers in which a tracked island may be missing before it is considered dead
+    #[structopt(long, default_value = "0")]
+    max_gap: usize,
 }
 
-fn main() {
-    let opt = Opt::from_args();
-
-    let delta = opt.delta;
-    let input_dir = opt.input_dir;
-    let input_paths = std::fs::read_dir(input_dir).unwrap();
-    let inputs: Vec<_> = input_paths
-        .into_iter()
-        .filter(|path| match path {
-            Ok(dir_entry) => {
-                let pb = dir_entry.path();
-                match pb.extension() {
-                    Some(extension) => extension == "txt",
-                    _ => false,
-                }
-            }
-            _ => false,
-        })
-        .collect();
+/// How islands are written as GeoJSON, WKT or WKB
+#[derive(Debug, StructOpt)]
+struct IslandsOpt {
+    /// Also writes the islands of every layer to a file of their own, named after --output and the layer
+    #[structopt(long)]
+    per_layer: bool,
 
-    match opt.algorithm.as_ref() {
-        "counting" => {
-            println!("Counting the number of islands over time");
-            println!("t,\t islands");
-            for i in 0..inputs.len() {
-                let input = inputs[i].as_ref();
-                let network = io::read_network(delta, &input.unwrap().path()).unwrap();
-                println!("{},\t {}", i, network.polygons().len());
-            }
-        }
-        "centroid" => {
-            println!("Using the polygonal centroid algorithm");
-            compute_reeb_graph(inputs, delta, (opt.x, opt.y), opt.start_time, 0).unwrap().to_ipe();
-        }
-        "disk" => {
-            println!("Using the smallest enclosing disk centroid algorithm");
-            compute_reeb_graph(inputs, delta, (opt.x, opt.y), opt.start_time, 1).unwrap().to_ipe();
+    /// Unix time in seconds of layer 0, used for the time property of the islands
+    #[structopt(long, default_value = "0")]
+    origin: i64,
+
+    /// Seconds between consecutive layers, used for the time property of the islands
+    #[structopt(long, default_value = "86400")]
+    interval: i64,
+
+    #[structopt(flatten)]
+    simplify: SimplifyOpt,
+}
+
+/// How the boundaries of the islands are simplified before they are written
+#[derive(Debug, StructOpt)]
+struct SimplifyOpt {
+    /// Tolerance for simplifying the island boundaries without making neighbouring islands overlap, a distance for
+    /// douglas-peucker and an area for visvalingam-whyatt (0 to keep every vertex)
+    #[structopt(long, default_value = "0", validator = non_negative)]
+    simplify: f64,
+
+    /// Algorithm used by --simplify: douglas-peucker (dp) or visvalingam-whyatt (vw)
+    #[structopt(long, default_value = "douglas-peucker")]
+    simplification: Simplification,
+}
+
+/// Output formats of the commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Csv,
+    Json,
+    Ipe,
+    Svg,
+    Geojson,
+    Wkt,
+    Wkb,
+    Ttga,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "ipe" => Ok(Format::Ipe),
+            "svg" => Ok(Format::Svg),
+            "geojson" => Ok(Format::Geojson),
+            "wkt" => Ok(Format::Wkt),
+            "wkb" => Ok(Format::Wkb),
+            "ttga" => Ok(Format::Ttga),
+            _ => Err(format!(
+                "unknown format {}, expected text, csv, json, ipe, svg, geojson, wkt, wkb or ttga",
+                s
+            )),
         }
-        _ => println!("Algorithm not found."),
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone)]
-struct State {
-    /// Id of the parent critical point
-    parent_id: usize,
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Format::Text => "text",
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::Ipe => "ipe",
+            Format::Svg => "svg",
+            Format::Geojson => "geojson",
+            Format::Wkt => "wkt",
+            Format::Wkb => "wkb",
+            Format::Ttga => "ttga",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Format {
+    /// Returns whether the format describes the islands themselves rather than results about them
+    fn is_islands(self) -> bool {
+        matches!(self, Format::Geojson | Format::Wkt | Format::Wkb)
+    }
+}
 
-    /// The next layer to match for
-    next_layer: usize,
+impl OutputOpt {
+    /// Returns the requested format, or the first of the `supported` formats if none was given
+    fn format(&self, command: &str, supported: &[Format]) -> Result<Format, String> {
+        let format = self.format.unwrap_or(supported[0]);
+        if supported.contains(&format) {
+            Ok(format)
+        } else {
+            let names: Vec<_> = supported.iter().map(Format::to_string).collect();
+            Err(format!(
+                "{} cannot write {}, expected one of {}",
+                command,
+                format,
+                names.join(", ")
+            ))
+        }
+    }
 
-    /// Index of the layer containing the polygon whose centroid we need to compute
-    layer: usize,
+    /// Opens the output file, or standard output if no file was given
+    fn open(&self) -> std::io::Result<Box<dyn Write>> {
+        open(self.output.as_deref())
+    }
+}
 
-    /// The index of the polygon in the layer
-    index: usize,
+fn open(path: Option<&Path>) -> std::io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => {
+            let file = File::create(path).map_err(|error| {
+                std::io::Error::new(error.kind(), format!("could not create {:?}: {}", path, error))
+            })?;
+            Box::new(BufWriter::new(file))
+        }
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    })
 }
 
-fn compute_reeb_graph(
-    inputs: Vec<Result<DirEntry, Error>>,
-    delta: f64,
-    start_point: (f64, f64),
-    start_time: usize,
-    method: i32,
-) -> Option<ReebGraph> {
-    if start_time >= inputs.len() {
-        println!("Start time too large, specify a number between 0 and {}", inputs.len() - 1);
-        return None
+fn positive(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(value) if value > 0. => Ok(()),
+        _ => Err(format!("expected a positive number, got {}", value)),
     }
+}
+
+fn non_negative(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(value) if value >= 0. => Ok(()),
+        _ => Err(format!("expected a non-negative number, got {}", value)),
+    }
+}
+
+fn main() {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let matches = Opt::clap().get_matches_from(&args);
+    let mut opt = Opt::from_clap(&matches);
+    if let Some(path) = &opt.config {
+        match config::merge(path, args, &matches) {
+            Ok(args) => opt = Opt::from_clap(&Opt::clap().get_matches_from(args)),
+            Err(message) => {
+                logger::init(logger::level(opt.verbose, opt.quiet));
+                error!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+    logger::init(logger::level(opt.verbose, opt.quiet));
+    if let Err(message) = run(opt) {
+        error!("{}", message);
+        std::process::exit(1);
+    }
+}
+
+fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
+    let seed = opt.seed.unwrap_or_else(|| thread_rng().gen::<u32>() as u64);
+    let mut rng = StdRng::seed_from_u64(seed);
 
-    let mut start_layer = 0;
+    match &opt.command {
+        Command::Count { input, output } => {
+            let format = output.format("count", &[Format::Text, Format::Csv, Format::Json])?;
+            let (inputs, times) = input.layers()?;
+            let mut manifest = Manifest::new("count", seed);
+            input.record(&mut manifest, inputs, times);
+            output.record(&mut manifest, format);
+            write_manifest(&manifest, &opt.manifest);
 
-    let mut found = false;
-    let mut index = 0;
+            let started = Instant::now();
+            let mut counts = Vec::new();
+            let mut archive = ArchiveReader::for_inputs(&manifest.inputs);
+            for (position, &i) in manifest.times.iter().enumerate() {
+                let islands = read_islands(&manifest.inputs[i], &input.source, archive.as_mut())?;
+                counts.push((i, islands.len()));
+                info!(
+                    "Layer {}: {} islands, {} layers queued, {:.2?} elapsed",
+                    i,
+                    counts[position].1,
+                    manifest.times.len() - position - 1,
+                    started.elapsed()
+                );
+            }
+            let mut out = output.open()?;
+            match format {
+                Format::Csv => statistics::write_counts_csv(&counts, &mut out)?,
+                Format::Json => statistics::write_counts_json(&counts, &mut out)?,
+                _ => statistics::write_counts_text(&counts, &mut out)?,
+            }
+            out.flush()?;
+        }
+        Command::Track {
+            input,
+            output,
+            track,
+            islands,
+        } => {
+            let supported = [
+                Format::Ipe,
+                Format::Csv,
+                Format::Json,
+                Format::Geojson,
+                Format::Wkt,
+                Format::Wkb,
+            ];
+            let format = output.format("track", &supported)?;
+            let (inputs, times) = input.layers()?;
+            let options = TrackingOptions {
+                delta: input.source.delta,
+                level: input.source.level,
+                cleaning: input.source.cleaning.cleaning(),
+                method: track.method,
+                matching: Matching::new(track.matching, track.max_cost, track.split_penalty),
+                direction: track.direction,
+                max_gap: track.max_gap,
+                tolerance: track.tolerance,
+            };
+
+            let mut manifest = Manifest::new("track", seed);
+            input.record(&mut manifest, inputs, times);
+            output.record(&mut manifest, format);
+            track.record(&mut manifest);
+            if format.is_islands() {
+                islands.record(&mut manifest);
+            }
+            write_manifest(&manifest, &opt.manifest);
+            let (inputs, times) = (&manifest.inputs, &manifest.times);
+
+            match &track.seeds {
+                Some(path) => {
+                    if (format == Format::Csv || format == Format::Json) && output.output.is_none() {
+                        return Err(format!("tracking seeds as {} needs an --output path", format).into());
+                    }
+                    let seeds = seeds::read_seeds(path)
+                        .map_err(|error| format!("could not read the seeds from {:?}: {}", path, error))?;
+                    let tracked = track_seeds(inputs, &seeds, input.start_time, times, &options, &mut rng);
+                    for (name, error) in &tracked.failed {
+                        warn!("Skipping seed {}: {}", name, error);
+                    }
+                    if tracked.tracked.is_empty() {
+                        return Err("none of the seeds could be tracked".into());
+                    }
+                    if format.is_islands() {
+                        let tracked: Vec<_> = tracked.tracked.iter().collect();
+                        return write_tracked_islands(&tracked, &manifest, input, output, format, islands);
+                    }
+                    for tracked in &tracked.tracked {
+                        if tracked.names.len() > 1 {
+                            info!(
+                                "Seeds {} lie in the same island, tracking them together",
+                                tracked.names.join(", ")
+                            );
+                        }
+                    }
+                    write_labelled(tracked.tracked, format, output)?;
+                }
+                None => {
+                    let region = match track.bounding_box[..] {
+                        [xmin, ymin, xmax, ymax] => Region::bounding_box((xmin, ymin), (xmax, ymax)),
+                        _ => Region::Point((track.x, track.y)),
+                    };
+                    let mut tracked = compute_reeb_graph(inputs, &region, input.start_time, times, &options, &mut rng)
+                        .map_err(|error| format!("could not track the island of {:?}: {}", region, error))?;
+                    if format.is_islands() {
+                        let tracked: Vec<_> = tracked.iter().collect();
+                        return write_tracked_islands(&tracked, &manifest, input, output, format, islands);
+                    }
+                    if tracked.len() == 1 {
+                        write_tracked(tracked.remove(0), format, &mut output.open()?)?;
+                    } else if (format == Format::Csv || format == Format::Json) && output.output.is_none() {
+                        let message = format!(
+                            "the seed refers to {} islands, writing them as {}",
+                            tracked.len(),
+                            format
+                        );
+                        return Err(format!("{} needs an --output path", message).into());
+                    } else {
+                        write_labelled(tracked, format, output)?;
+                    }
+                }
+            }
+        }
+        Command::Stats {
+            input,
+            output,
+            layers_output,
+        } => {
+            let format = output.format("stats", &[Format::Csv])?;
+            let (inputs, times) = input.layers()?;
+            let mut manifest = Manifest::new("stats", seed);
+            input.record(&mut manifest, inputs, times);
+            output.record(&mut manifest, format);
+            if let Some(path) = layers_output {
+                manifest.parameter("layers_output", path);
+            }
+            write_manifest(&manifest, &opt.manifest);
 
-    let input = inputs[start_time].as_ref();
-    let mut islands = io::read_network(delta, &input.unwrap().path())
-        .unwrap()
-        .polygons();
+            let mut islands_out = output.open()?;
+            let mut layers_out = match layers_output {
+                Some(path) => Some(open(Some(path))?),
+                None => None,
+            };
+            statistics::write_statistics(
+                &manifest.inputs,
+                &manifest.times,
+                input.source.delta,
+                input.source.level,
+                &input.source.cleaning.cleaning(),
+                &mut islands_out,
+                layers_out.as_mut(),
+                &mut rng,
+            )?;
+        }
+        Command::Render {
+            source,
+            layer,
+            simplify,
+            output,
+        } => {
+            let format = output.format("render", &[Format::Ipe, Format::Svg])?;
+            let inputs = source.inputs()?;
+            let input = inputs
+                .get(*layer)
+                .ok_or_else(|| format!("layer {} does not exist, there are {} layers", layer, inputs.len()))?;
+            let mut manifest = Manifest::new("render", seed);
+            source.record(&mut manifest);
+            manifest.parameter("layer", *layer);
+            simplify.record(&mut manifest);
+            output.record(&mut manifest, format);
+            manifest.times = vec![*layer];
+            manifest.inputs = inputs.clone();
+            write_manifest(&manifest, &opt.manifest);
+
+            let islands = simplify.apply(read_islands(input, source, None)?);
+            let mut out = output.open()?;
+            match format {
+                Format::Svg => {
+                    let polygons: Vec<_> = islands
+                        .iter()
+                        .map(|island| geo::Polygon::new(island.vertices().to_vec().into(), vec![]))
+                        .collect();
+                    let svg = polygons
+                        .to_svg()
+                        .with_fill_color(Color::Named("lightgray"))
+                        .with_stroke_color(Color::Named("black"))
+                        .with_stroke_width(1.);
+                    writeln!(out, "{}", svg)?;
+                }
+                _ => {
+                    writeln!(out, "<ipeselection pos=\"0 0\">")?;
+                    for island in &islands {
+                        island.write_ipe(&mut out)?;
+                    }
+                    writeln!(out, "</ipeselection>")?;
+                }
+            }
+            out.flush()?;
+        }
+        Command::Preprocess {
+            network,
+            delta,
+            cleaning,
+            output,
+        } => {
+            let format = output.format("preprocess", &[Format::Ipe, Format::Ttga])?;
+            let mut manifest = Manifest::new("preprocess", seed);
+            manifest.parameter("network", network);
+            manifest.parameter("delta", *delta);
+            cleaning.record(&mut manifest);
+            output.record(&mut manifest, format);
+            manifest.inputs = vec![Input::File(network.clone())];
+            write_manifest(&manifest, &opt.manifest);
 
-    'search_loop: for layer in start_time..inputs.len() {
-        for j in 0..islands.len() {
-            let island = &islands[j];
-            if island.contains(&start_point) {
-                found = true;
-                index = j;
-                start_layer = layer;
-                break 'search_loop;
+            let mut graph =
+                io::read_graph(*delta, network).map_err(|error| format!("could not read {:?}: {}", network, error))?;
+            let report = graph.clean(&cleaning.cleaning());
+            if !report.is_empty() {
+                warn!("Cleaned {}: {}", network.display(), report);
+            }
+            graph.reduce();
+            let mut out = output.open()?;
+            match format {
+                Format::Ttga => graph.write_ttga(&mut out)?,
+                _ => graph.write_ipe(&mut out)?,
             }
+            out.flush()?;
         }
+        Command::Persistence { input, output } => {
+            let format = output.format("persistence", &[Format::Csv, Format::Json])?;
+            let (inputs, times) = input.layers()?;
+            let mut manifest = Manifest::new("persistence", seed);
+            input.record(&mut manifest, inputs, times);
+            output.record(&mut manifest, format);
+            write_manifest(&manifest, &opt.manifest);
 
-        if layer + 1 < inputs.len() {
-            let input = inputs[layer + 1].as_ref();
-            islands = io::read_network(delta, &input.unwrap().path())
-                .unwrap()
-                .polygons();
+            let mut rows = Vec::new();
+            let mut archive = ArchiveReader::for_inputs(&manifest.inputs);
+            for &layer in &manifest.times {
+                let islands = read_islands(&manifest.inputs[layer], &input.source, archive.as_mut())?;
+                rows.extend(IslandPersistence::from_layer(layer, &islands));
+            }
+            let mut out = output.open()?;
+            match format {
+                Format::Json => statistics::write_persistence_json(&rows, &mut out)?,
+                _ => statistics::write_persistence_csv(&rows, &mut out)?,
+            }
+            out.flush()?;
+        }
+        Command::Export { input, output, islands } => {
+            let format = output.format("export", &[Format::Geojson, Format::Wkt, Format::Wkb])?;
+            let (inputs, times) = input.layers()?;
+            let mut manifest = Manifest::new("export", seed);
+            input.record(&mut manifest, inputs, times);
+            output.record(&mut manifest, format);
+            islands.record(&mut manifest);
+            write_manifest(&manifest, &opt.manifest);
+
+            let mut layers = Vec::new();
+            let mut archive = ArchiveReader::for_inputs(&manifest.inputs);
+            for &layer in &manifest.times {
+                let read = read_islands(&manifest.inputs[layer], &input.source, archive.as_mut())?;
+                layers.push((layer, islands.simplify.apply(read)));
+            }
+            let features: Vec<_> = layers
+                .iter()
+                .flat_map(|(layer, islands)| {
+                    islands.iter().enumerate().map(move |(index, island)| IslandFeature {
+                        island,
+                        layer: *layer,
+                        index,
+                        delta: input.source.delta,
+                        level: input.source.level,
+                        track: None,
+                        seed: None,
+                    })
+                })
+                .collect();
+            write_islands(&features, format, output, islands)?;
+        }
+        Command::Validate { input_dir, output } => {
+            let format = output.format("validate", &[Format::Text, Format::Csv, Format::Json])?;
+            let inputs = io::list_inputs(input_dir)
+                .map_err(|error| format!("could not list the networks in {:?}: {}", input_dir, error))?;
+            let mut manifest = Manifest::new("validate", seed);
+            manifest.parameter("input_dir", input_dir);
+            output.record(&mut manifest, format);
+            manifest.times = (0..inputs.len()).collect();
+            manifest.inputs = inputs;
+            write_manifest(&manifest, &opt.manifest);
+
+            let mut reports = Vec::new();
+            let mut archive = ArchiveReader::for_inputs(&manifest.inputs);
+            for (layer, input) in manifest.inputs.iter().enumerate() {
+                let report = match io::validate_layer(input, archive.as_mut()) {
+                    Ok(Some(report)) => report,
+                    Ok(None) => {
+                        info!("Layer {}: {} is a grid, skipping it", layer, input);
+                        continue;
+                    }
+                    Err(error) => Report::unreadable(error.to_string()),
+                };
+                info!("Layer {}: {} problems in {}", layer, report.problems.len(), input);
+                reports.push((layer, input, report));
+            }
+            let mut out = output.open()?;
+            match format {
+                Format::Csv => validation::write_csv(&reports, &mut out)?,
+                Format::Json => validation::write_json(&reports, &mut out)?,
+                _ => validation::write_text(&reports, &mut out)?,
+            }
+            out.flush()?;
+
+            let invalid = reports.iter().filter(|(_, _, report)| !report.is_valid()).count();
+            if invalid > 0 {
+                return Err(format!("{} of {} networks have problems", invalid, reports.len()).into());
+            }
         }
     }
+    Ok(())
+}
 
-    if !found {
-        println!("Island containing {:?} not found", start_point);
-        return None;
+impl SourceOpt {
+    /// Lists the files in the input directory, or the pages of the stack
+    fn inputs(&self) -> Result<Vec<Input>, String> {
+        match &self.stack {
+            Some(stack) => io::stack_inputs(
+                stack,
+                self.baseline.as_deref(),
+                self.first_frame.unwrap_or(0),
+                self.last_frame,
+            )
+            .map_err(|error| format!("could not read the stack {:?}: {}", stack, error)),
+            None => io::list_inputs(&self.input_dir)
+                .map_err(|error| format!("could not list the networks in {:?}: {}", self.input_dir, error)),
+        }
     }
 
-    let mut reeb = ReebGraph::new(&CriticalPoint::new(0), start_point.0, start_layer as i32);
+    fn record(&self, manifest: &mut Manifest) {
+        match &self.stack {
+            Some(stack) => {
+                manifest.parameter("stack", stack);
+                if let Some(baseline) = &self.baseline {
+                    manifest.parameter("baseline", baseline);
+                }
+                manifest.parameter("first_frame", self.first_frame.unwrap_or(0));
+                if let Some(last_frame) = self.last_frame {
+                    manifest.parameter("last_frame", last_frame);
+                }
+            }
+            None => manifest.parameter("input_dir", &self.input_dir),
+        }
+        manifest.parameter("delta", self.delta);
+        manifest.parameter("level", self.level);
+        self.cleaning.record(manifest);
+    }
+}
 
-    let mut queue = VecDeque::new();
-    queue.push_back(State {
-        parent_id: 0,
-        next_layer: start_layer + 1,
-        layer: start_layer,
-        index,
-    });
+impl CleaningOpt {
+    fn cleaning(&self) -> Cleaning {
+        Cleaning {
+            snap: self.snap,
+            planarize: self.planarize,
+        }
+    }
 
-    let mut included = FxHashSet::default();
-    included.insert(State {
-        parent_id: 0,
-        next_layer: start_layer + 1,
-        layer: start_layer,
-        index,
-    });
+    fn record(&self, manifest: &mut Manifest) {
+        manifest.parameter("snap", self.snap);
+        manifest.parameter("planarize", self.planarize);
+    }
+}
 
-    let mut old_layer = start_layer;
-    let mut old_islands = islands;
-    let mut new_layer = start_layer + 1;
-    let next_input = inputs[start_layer + 1].as_ref();
-    let mut new_islands = io::read_network(delta, &next_input.unwrap().path())
-        .unwrap()
-        .polygons();
+impl InputOpt {
+    /// Lists the layers and schedules the layers to compare
+    fn layers(&self) -> Result<(Vec<Input>, Vec<usize>), Box<dyn Error>> {
+        let inputs = self.source.inputs()?;
+        let times = schedule(inputs.len(), self.start_time, self.end_time, self.stride, &self.layers)?;
+        Ok((inputs, times))
+    }
 
-    let mut start_ids = old_islands.len();
+    /// Records the effective options together with the listed `inputs` and the scheduled `times`
+    fn record(&self, manifest: &mut Manifest, inputs: Vec<Input>, times: Vec<usize>) {
+        self.source.record(manifest);
+        manifest.parameter("start_time", self.start_time);
+        if let Some(end_time) = self.end_time {
+            manifest.parameter("end_time", end_time);
+        }
+        manifest.parameter("stride", self.stride);
+        if !self.layers.is_empty() {
+            manifest.parameter("layers", &self.layers);
+        }
+        manifest.inputs = inputs;
+        manifest.times = times;
+    }
+}
 
-    while let Some(State {
-        parent_id,
-        next_layer,
-        layer,
-        index,
-    }) = queue.pop_front()
-    {
-        if next_layer == inputs.len() {
-            continue;
+impl OutputOpt {
+    fn record(&self, manifest: &mut Manifest, format: Format) {
+        if let Some(path) = &self.output {
+            manifest.parameter("output", path);
         }
+        manifest.parameter("format", format.to_string());
+    }
+}
 
-        if layer != old_layer && next_layer != new_layer {
-            start_ids += old_islands.len();
-            old_layer = new_layer;
-            old_islands = new_islands;
+impl IslandsOpt {
+    fn record(&self, manifest: &mut Manifest) {
+        manifest.parameter("per_layer", self.per_layer);
+        manifest.parameter("origin", self.origin);
+        manifest.parameter("interval", self.interval);
+        self.simplify.record(manifest);
+    }
+}
 
-            new_layer = next_layer;
-            let next_input = inputs[next_layer].as_ref();
-            new_islands = io::read_network(delta, &next_input.unwrap().path())
-                .unwrap()
-                .polygons();
+impl SimplifyOpt {
+    /// Simplifies the boundaries of the `islands` of a layer, if a tolerance is given
+    fn apply(&self, islands: Vec<Polygon>) -> Vec<Polygon> {
+        if self.simplify > 0. {
+            simplify_islands(&islands, self.simplification, self.simplify)
+        } else {
+            islands
         }
+    }
 
-        let old_centroid = if method == 0 {
-            old_islands[index].centroid().unwrap()
-        } else {
-            old_islands[index].smallest_disk_centroid().unwrap()
+    fn record(&self, manifest: &mut Manifest) {
+        manifest.parameter("simplify", self.simplify);
+        let simplification = match self.simplification {
+            Simplification::DouglasPeucker => "douglas-peucker",
+            Simplification::VisvalingamWhyatt => "visvalingam-whyatt",
         };
+        manifest.parameter("simplification", simplification);
+    }
+}
 
-        for poly_new in 0..new_islands.len() {
-            let new_centroid = if method == 0 {
-                new_islands[poly_new].centroid().unwrap()
-            } else {
-                new_islands[poly_new].smallest_disk_centroid().unwrap()
-            };
+impl TrackOpt {
+    fn record(&self, manifest: &mut Manifest) {
+        manifest.parameter("x", self.x);
+        manifest.parameter("y", self.y);
+        if !self.bounding_box.is_empty() {
+            manifest.parameter("box", &self.bounding_box);
+        }
+        manifest.parameter("tolerance", self.tolerance);
+        if let Some(path) = &self.seeds {
+            manifest.parameter("seeds", path);
+        }
+        let method = match self.method {
+            Method::Centroid => "centroid",
+            Method::Disk => "disk",
+        };
+        manifest.parameter("method", method);
+        let direction = match self.direction {
+            Direction::Forward => "forward",
+            Direction::Backward => "backward",
+            Direction::Both => "both",
+        };
+        manifest.parameter("direction", direction);
+        let matching = match self.matching {
+            MatchingKind::Containment => "containment",
+            MatchingKind::Optimal => "optimal",
+        };
+        manifest.parameter("matching", matching);
+        manifest.parameter("max_cost", self.max_cost);
+        manifest.parameter("split_penalty", self.split_penalty);
+        manifest.parameter("max_gap", self.max_gap);
+    }
+}
+
+/// Reads the islands of a layer as given by `source`, with `archive` for the members of an archive, naming the layer
+/// in the error
+fn read_islands(
+    input: &Input,
+    source: &SourceOpt,
+    archive: Option<&mut ArchiveReader>,
+) -> Result<Vec<Polygon>, String> {
+    io::read_islands(input, source.delta, source.level, &source.cleaning.cleaning(), archive)
+        .map_err(|error| format!("could not read {}: {}", input, error))
+}
+
+/// Writes the manifest, a failure is reported but does not stop the run
+fn write_manifest(manifest: &Manifest, path: &Path) {
+    if let Err(error) = manifest.write(path) {
+        warn!("Could not write the manifest to {:?}: {}", path, error);
+    }
+}
 
-            let id = start_ids + poly_new;
+/// Writes the Reeb graph of the tracked island (ipe) or its time series (csv, json) to `out`
+fn write_tracked<W: Write>(tracked: Tracked, format: Format, out: &mut W) -> std::io::Result<()> {
+    match format {
+        Format::Csv => lineage::write_csv(&lineage::lineage(&tracked.reeb), out)?,
+        Format::Json => lineage::write_json(&lineage::lineage(&tracked.reeb), out)?,
+        _ => tracked.reeb.write_ipe(out)?,
+    }
+    out.flush()
+}
 
-            let old_contains_new = old_islands[index].contains(&new_centroid); // if so: split or normal
-            let new_contains_old = new_islands[poly_new].contains(&old_centroid); // if so: merge or normal
-            if old_contains_new || new_contains_old {
-                reeb.add_point(layer as i32, &CriticalPoint::new(parent_id as i32), &CriticalPoint::new(id as i32), new_centroid.0);
+/// Writes the islands of the `tracked` islands, numbered in the given order, the layers are read again
+fn write_tracked_islands(
+    tracked: &[&Tracked],
+    manifest: &Manifest,
+    input: &InputOpt,
+    output: &OutputOpt,
+    format: Format,
+    options: &IslandsOpt,
+) -> Result<(), Box<dyn Error>> {
+    // the layers are read in their order, so the members of an archive are read in one pass
+    let wanted: BTreeSet<_> = tracked
+        .iter()
+        .flat_map(|tracked| &tracked.islands)
+        .map(|&(layer, _)| layer)
+        .collect();
+    let mut archive = ArchiveReader::for_inputs(&manifest.inputs);
+    let mut layers = BTreeMap::new();
+    for layer in wanted {
+        let islands = read_islands(&manifest.inputs[layer], &input.source, archive.as_mut())?;
+        layers.insert(layer, options.simplify.apply(islands));
+    }
+    let names: Vec<_> = tracked.iter().map(|tracked| tracked.names.join("+")).collect();
+    let features = geojson::tracked_features(tracked, &names, &layers, input.source.delta, input.source.level);
+    write_islands(&features, format, output, options)
+}
 
-                let state = State {
-                    index: poly_new,
-                    next_layer: new_layer + 1,
-                    layer: new_layer,
-                    parent_id: id,
-                };
+/// Writes the `features`, sorted by layer, to the output, together with their time. With --per-layer, the features of
+/// every layer are also written to a file of their own.
+fn write_islands(
+    features: &[IslandFeature],
+    format: Format,
+    output: &OutputOpt,
+    options: &IslandsOpt,
+) -> Result<(), Box<dyn Error>> {
+    if options.per_layer {
+        let path = output.output.as_ref().ok_or("--per-layer needs an --output path")?;
+        for layer in features.chunk_by(|a, b| a.layer == b.layer) {
+            let mut out = open(Some(&labelled(path, Some(&layer[0].layer.to_string()))))?;
+            write_features(layer, format, None, &mut out)?;
+        }
+    }
+    let clock = Clock {
+        origin: options.origin,
+        interval: options.interval,
+    };
+    write_features(features, format, Some(&clock), &mut output.open()?)?;
+    Ok(())
+}
 
-                if !included.contains(&state) {
-                    included.insert(state.clone());
-                    queue.push_back(state);
+/// Writes the `features` as a GeoJSON collection, or as CSV with the geometry as WKT or hexadecimal WKB in the last
+/// column. If a `clock` is given, the time of the layer is added to every feature.
+fn write_features(
+    features: &[IslandFeature],
+    format: Format,
+    clock: Option<&Clock>,
+    out: &mut Box<dyn Write>,
+) -> std::io::Result<()> {
+    match format {
+        Format::Geojson => geojson::write_geojson(features, clock, out)?,
+        Format::Wkb => geojson::write_csv(features, Encoding::Wkb, clock, out)?,
+        _ => geojson::write_csv(features, Encoding::Wkt, clock, out)?,
+    }
+    out.flush()
+}
+
+/// Writes every tracked island to the output file labelled with its seeds, or to standard output preceded by a comment
+/// naming them. Islands tracked from the same seeds, or from the seed region given on the command line, are told apart
+/// by their start island.
+fn write_labelled(tracked: Vec<Tracked>, format: Format, output: &OutputOpt) -> Result<(), Box<dyn Error>> {
+    let names: Vec<_> = tracked.iter().map(|tracked| tracked.names.join("+")).collect();
+    let mut stdout = None;
+    for (tracked, name) in tracked.into_iter().zip(&names) {
+        let label = if names.iter().filter(|other| *other == name).count() == 1 {
+            name.clone()
+        } else if name.is_empty() {
+            format!("island{}", tracked.start_island)
+        } else {
+            format!("{}-island{}", name, tracked.start_island)
+        };
+        match &output.output {
+            Some(path) => {
+                let mut out = open(Some(&labelled(path, Some(&label))))?;
+                write_tracked(tracked, format, &mut out)?;
+            }
+            None => {
+                let out = stdout.get_or_insert(open(None)?);
+                if name.is_empty() {
+                    writeln!(
+                        out,
+                        "<!-- island: {} of layer {} -->",
+                        tracked.start_island, tracked.start_layer
+                    )?;
+                } else {
+                    writeln!(out, "<!-- seed: {} -->", label)?;
                 }
+                write_tracked(tracked, format, out)?;
             }
         }
     }
+    Ok(())
+}
 
-    return Some(reeb);
+/// Adds `name` to the file name of `path`, e.g. `lineage.csv` becomes `lineage-name.csv`.
+/// Characters other than letters, digits, `-` and `_` in the name are replaced by `_`.
+fn labelled(path: &Path, name: Option<&str>) -> PathBuf {
+    let name = match name {
+        Some(name) => name,
+        None => return path.to_path_buf(),
+    };
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let mut file_name = format!("{}-{}", stem, name);
+    if let Some(extension) = path.extension() {
+        file_name = format!("{}.{}", file_name, extension.to_string_lossy());
+    }
+    path.with_file_name(file_name)
 }
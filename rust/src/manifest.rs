@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{BufWriter, Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use fnv::FnvHasher;
+use island_tracker::Input;
+
+/// Everything needed to reproduce a run of the tool, written as TOML next to the results
+pub struct Manifest {
+    /// The subcommand that was run
+    pub command: String,
+    pub seed: u64,
+    /// The effective options of the subcommand as keys and TOML values, e.g. `("delta", "100.0")`
+    pub parameters: Vec<(String, String)>,
+    /// The layers that were compared, as indices into `inputs`
+    pub times: Vec<usize>,
+    pub inputs: Vec<Input>,
+}
+
+impl Manifest {
+    pub fn new(command: &str, seed: u64) -> Manifest {
+        Manifest {
+            command: command.to_string(),
+            seed,
+            parameters: Vec::new(),
+            times: Vec::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Records the option `key` with the TOML `value`
+    pub fn parameter<V: ToToml>(&mut self, key: &str, value: V) {
+        self.parameters.push((key.to_string(), value.to_toml()));
+    }
+
+    /// Writes the manifest to the file at `path`, hashing every input file
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> Result<()> {
+        writeln!(out, "# Island-Tracker run manifest")?;
+        writeln!(out, "version = {}", toml_string(env!("CARGO_PKG_VERSION")))?;
+        writeln!(out, "command = {}", toml_string(&self.command))?;
+        writeln!(out, "seed = {}", self.seed)?;
+        writeln!(out, "compared_layers = {}", self.times.to_toml())?;
+
+        // the effective options, so the manifest can be passed back to the tool with --config
+        writeln!(out)?;
+        writeln!(out, "[{}]", self.command)?;
+        for (key, value) in &self.parameters {
+            writeln!(out, "{} = {}", key, value)?;
+        }
+
+        // the pages of a stack share their file, which is only hashed once
+        let mut hashes = HashMap::new();
+        let mut hash = |path: &Path| -> Result<u64> {
+            if let Some(hash) = hashes.get(path) {
+                return Ok(*hash);
+            }
+            let hash = hash_file(path)?;
+            hashes.insert(path.to_path_buf(), hash);
+            Ok(hash)
+        };
+        for input in &self.inputs {
+            writeln!(out)?;
+            writeln!(out, "[[inputs]]")?;
+            writeln!(out, "path = {}", input.path().to_toml())?;
+            match input {
+                Input::Page { page, baseline, .. } => {
+                    writeln!(out, "page = {}", page)?;
+                    if let Some(baseline) = baseline {
+                        writeln!(out, "baseline = {}", baseline.path.to_toml())?;
+                        writeln!(out, "baseline_fnv64 = \"{:016x}\"", hash(&baseline.path)?)?;
+                    }
+                }
+                Input::Member { member, .. } => writeln!(out, "member = {}", member.to_toml())?,
+                Input::File(_) => {}
+            }
+            writeln!(out, "fnv64 = \"{:016x}\"", hash(input.path())?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Values that can be written to the manifest
+pub trait ToToml {
+    fn to_toml(&self) -> String;
+}
+
+impl ToToml for f64 {
+    fn to_toml(&self) -> String {
+        if self.is_nan() {
+            "nan".to_string()
+        } else if self.is_infinite() {
+            if *self > 0. { "inf" } else { "-inf" }.to_string()
+        } else {
+            format!("{:?}", self)
+        }
+    }
+}
+
+impl ToToml for usize {
+    fn to_toml(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToToml for i64 {
+    fn to_toml(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToToml for bool {
+    fn to_toml(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToToml for str {
+    fn to_toml(&self) -> String {
+        toml_string(self)
+    }
+}
+
+impl ToToml for String {
+    fn to_toml(&self) -> String {
+        toml_string(self)
+    }
+}
+
+impl ToToml for Path {
+    fn to_toml(&self) -> String {
+        toml_string(&self.display().to_string())
+    }
+}
+
+impl ToToml for PathBuf {
+    fn to_toml(&self) -> String {
+        self.as_path().to_toml()
+    }
+}
+
+impl<T: ToToml> ToToml for [T] {
+    fn to_toml(&self) -> String {
+        let values: Vec<_> = self.iter().map(ToToml::to_toml).collect();
+        format!("[{}]", values.join(", "))
+    }
+}
+
+impl<T: ToToml, const N: usize> ToToml for [T; N] {
+    fn to_toml(&self) -> String {
+        self[..].to_toml()
+    }
+}
+
+impl<T: ToToml> ToToml for Vec<T> {
+    fn to_toml(&self) -> String {
+        self.as_slice().to_toml()
+    }
+}
+
+impl<T: ToToml + ?Sized> ToToml for &T {
+    fn to_toml(&self) -> String {
+        (**self).to_toml()
+    }
+}
+
+/// Computes the 64-bit FNV-1a hash of the contents of the file at `path`
+pub fn hash_file(path: &Path) -> Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = FnvHasher::default();
+    let mut buffer = [0; 1 << 16];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.write(&buffer[..read]);
+    }
+    Ok(hasher.finish())
+}
+
+/// Formats `value` as a TOML basic string
+fn toml_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_string_escapes() {
+        assert_eq!(toml_string("networks\\a\"b"), "\"networks\\\\a\\\"b\"");
+    }
+
+    #[test]
+    fn manifest_without_inputs() {
+        let mut manifest = Manifest::new("track", 42);
+        manifest.parameter("delta", 100.);
+        manifest.parameter("start_point", [1.5, -2.]);
+        manifest.parameter("method", "disk");
+        manifest.times = vec![1, 3];
+        let mut out = Vec::new();
+        manifest.write_to(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("command = \"track\"\n"));
+        assert!(text.contains("method = \"disk\"\n"));
+        assert!(text.contains("delta = 100.0\n"));
+        assert!(text.contains("seed = 42\n"));
+        assert!(text.contains("start_point = [1.5, -2.0]\n"));
+        assert!(text.contains("compared_layers = [1, 3]\n"));
+        assert!(text.contains("\n[track]\n"));
+    }
+}
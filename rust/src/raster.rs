@@ -0,0 +1,381 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result};
+use std::path::Path;
+
+use fxhash::FxHashMap;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+use tiff::TiffError;
+
+use crate::geometry::Polygon;
+use crate::io::open_file;
+
+/// A grid of elevations, e.g. a detrended bed level, as written by `writer.py`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    /// Size of a cell along the x-axis (columns) and the y-axis (rows)
+    pub cell_size: (f64, f64),
+    /// The values row by row, `NAN` where the value is unknown
+    pub values: Vec<f64>,
+}
+
+/// A grid edge between grid point `(x, y)` and the next point in its row (`horizontal`) or in its column.
+/// Points are shifted by one so the grid is surrounded by a border of water points.
+type Edge = (usize, usize, bool);
+
+impl Grid {
+    /// Returns the value at column `x` and row `y`
+    pub fn value(&self, x: usize, y: usize) -> f64 {
+        self.values[y * self.width + x]
+    }
+
+    /// Adds the values of `other` to the values of `self`, e.g. a baseline to detrended values
+    pub fn add(&mut self, other: &Grid) -> Result<()> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Err(invalid_data(format!(
+                "cannot add a {}x{} grid to a {}x{} grid",
+                other.width, other.height, self.width, self.height
+            )));
+        }
+        for (value, other) in self.values.iter_mut().zip(&other.values) {
+            *value += other;
+        }
+        Ok(())
+    }
+
+    /// Returns the land regions, the cells above the water `level`, as polygons by marching squares.
+    /// Land connected to the border of the grid, such as the river banks, is not an island and lakes inside an
+    /// island are ignored. Unknown values are water. Grid point `(x, y)` lies at `(x * cell_width, y * cell_height)`.
+    pub fn islands(&self, level: f64) -> Vec<Polygon> {
+        // the next edge along the boundary of the land, which is on the left
+        let mut next: FxHashMap<Edge, Edge> = FxHashMap::default();
+        for y in 0..=self.height {
+            for x in 0..=self.width {
+                self.march(x, y, level, &mut next);
+            }
+        }
+
+        let mut islands = Vec::new();
+        let mut edges: Vec<_> = next.keys().copied().collect();
+        edges.sort_unstable();
+        for start in edges {
+            let mut ring = Vec::new();
+            let mut border = false;
+            let mut edge = start;
+            while let Some(to) = next.remove(&edge) {
+                let (point, on_border) = self.crossing(edge, level);
+                // neighbouring crossings coincide at grid points with a value equal to the level
+                if ring.last() != Some(&point) {
+                    ring.push(point);
+                }
+                border |= on_border;
+                edge = to;
+            }
+            if ring.len() > 1 && ring.first() == ring.last() {
+                ring.pop();
+            }
+            if ring.len() >= 3 && !border && signed_area(&ring) > 0. {
+                islands.push(Polygon::new(ring));
+            }
+        }
+        islands
+    }
+
+    /// Returns the value at the shifted grid point `(x, y)`, `NAN` on the border
+    fn shifted(&self, x: usize, y: usize) -> f64 {
+        if x == 0 || y == 0 || x > self.width || y > self.height {
+            f64::NAN
+        } else {
+            self.value(x - 1, y - 1)
+        }
+    }
+
+    /// Adds the boundary segments of the land in the square with smallest shifted grid point `(x, y)` to `next`
+    fn march(&self, x: usize, y: usize, level: f64, next: &mut FxHashMap<Edge, Edge>) {
+        // the corners and edges of the square in counterclockwise order, with y pointing up
+        let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+        let edges = [(x, y, true), (x + 1, y, false), (x, y + 1, true), (x, y, false)];
+        let values: Vec<_> = corners.iter().map(|&(x, y)| self.shifted(x, y)).collect();
+        let land: Vec<_> = values.iter().map(|&value| value > level).collect();
+
+        // the boundary leaves the land on edge i if the land is left behind when walking around the square
+        let exits: Vec<_> = (0..4).filter(|&i| land[i] && !land[(i + 1) % 4]).collect();
+        let entries: Vec<_> = (0..4).filter(|&i| !land[i] && land[(i + 1) % 4]).collect();
+        match exits.len() {
+            1 => {
+                next.insert(edges[exits[0]], edges[entries[0]]);
+            }
+            2 => {
+                // a saddle: the land corners are connected through the centre if its mean value is land
+                let centre = values.iter().sum::<f64>() / 4.;
+                for &exit in &exits {
+                    let following = (exit + 1) % 4;
+                    let preceding = (exit + 3) % 4;
+                    let entry = if centre > level { following } else { preceding };
+                    next.insert(edges[exit], edges[entry]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the point where the boundary crosses `edge`, and whether the edge touches the border of the grid
+    fn crossing(&self, (x, y, horizontal): Edge, level: f64) -> ((f64, f64), bool) {
+        let (x2, y2) = if horizontal { (x + 1, y) } else { (x, y + 1) };
+        let (a, b) = (self.shifted(x, y), self.shifted(x2, y2));
+        let t = if a.is_nan() {
+            1.
+        } else if b.is_nan() {
+            0.
+        } else {
+            (level - a) / (b - a)
+        };
+        let on_border = [(x, y), (x2, y2)]
+            .iter()
+            .any(|&(x, y)| x == 0 || y == 0 || x > self.width || y > self.height);
+        let (cell_width, cell_height) = self.cell_size;
+        let px = (x as f64 - 1. + t * (x2 - x) as f64) * cell_width;
+        let py = (y as f64 - 1. + t * (y2 - y) as f64) * cell_height;
+        ((px, py), on_border)
+    }
+}
+
+/// Returns the signed area of the ring, positive if it is counterclockwise
+fn signed_area(ring: &[(f64, f64)]) -> f64 {
+    let mut area = 0.;
+    for i in 0..ring.len() {
+        let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+        area += a.0 * b.1 - b.0 * a.1;
+    }
+    area / 2.
+}
+
+/// Reads a grid written by `writer.py`: a header `width height cell_width cell_height min max` followed by the
+/// rows of values separated by spaces. Values that are `nan` are unknown. Compressed grids are decompressed, see
+/// [`open_file`].
+pub fn read_grid(path: &Path) -> Result<Grid> {
+    parse_grid(open_file(path)?)
+}
+
+pub fn parse_grid<R: BufRead>(reader: R) -> Result<Grid> {
+    let mut lines = reader.lines();
+    let header = lines.next().ok_or_else(|| invalid_data("empty grid"))??;
+    let header: Vec<_> = header.split_whitespace().collect();
+    if header.len() < 4 {
+        return Err(invalid_data(format!("malformed grid header {:?}", header.join(" "))));
+    }
+    let width = header[0].parse::<usize>().map_err(invalid_data)?;
+    let height = header[1].parse::<usize>().map_err(invalid_data)?;
+    let cell_width = header[2].parse::<f64>().map_err(invalid_data)?;
+    let cell_height = header[3].parse::<f64>().map_err(invalid_data)?;
+
+    let mut values = Vec::with_capacity(width * height);
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = line
+            .split_whitespace()
+            .map(|value| value.parse::<f64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(invalid_data)?;
+        if row.len() != width {
+            return Err(invalid_data(format!(
+                "row {} has {} values, expected {}",
+                values.len() / width.max(1),
+                row.len(),
+                width
+            )));
+        }
+        values.extend(row);
+    }
+    if values.len() != width * height {
+        return Err(invalid_data(format!(
+            "the grid has {} rows, expected {}",
+            values.len() / width.max(1),
+            height
+        )));
+    }
+
+    Ok(Grid {
+        width,
+        height,
+        cell_size: (cell_width, cell_height),
+        values,
+    })
+}
+
+/// Reads page `page` (counted from 0) of the TIFF at `path`, e.g. a frame of a stack of bed levels, as a grid. The
+/// cell size is the pixel size of a GeoTIFF (its `ModelPixelScale` tag), or 1 without that tag. The position of the
+/// image (its tie points) is not read, so grid point `(0, 0)` is the first pixel. Only single band images are
+/// supported.
+pub fn read_tiff_page(path: &Path, page: usize) -> Result<Grid> {
+    let mut decoder = Decoder::new(BufReader::new(File::open(path)?)).map_err(tiff_error)?;
+    decoder.seek_to_image(page).map_err(tiff_error)?;
+    let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+    let values: Vec<f64> = match decoder.read_image().map_err(tiff_error)? {
+        DecodingResult::U8(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::U16(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::U32(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::U64(values) => values.into_iter().map(|value| value as f64).collect(),
+        DecodingResult::F32(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::F64(values) => values,
+        DecodingResult::I8(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::I16(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::I32(values) => values.into_iter().map(f64::from).collect(),
+        DecodingResult::I64(values) => values.into_iter().map(|value| value as f64).collect(),
+    };
+    let cell_size = match decoder.find_tag(Tag::ModelPixelScaleTag).map_err(tiff_error)? {
+        Some(scale) => match scale.into_f64_vec().map_err(tiff_error)?[..] {
+            [x, y, ..] if x > 0. && y > 0. => (x, y),
+            ref scale => {
+                return Err(invalid_data(format!(
+                    "page {} has an invalid pixel scale {:?}",
+                    page, scale
+                )))
+            }
+        },
+        None => (1., 1.),
+    };
+    let (width, height) = (width as usize, height as usize);
+    if values.len() != width * height {
+        return Err(invalid_data(format!(
+            "page {} has {} values per pixel, expected a single band",
+            page,
+            values.len() / (width * height).max(1)
+        )));
+    }
+    Ok(Grid {
+        width,
+        height,
+        cell_size,
+        values,
+    })
+}
+
+/// Returns the number of pages of the TIFF at `path`
+pub fn count_tiff_pages(path: &Path) -> Result<usize> {
+    let mut decoder = Decoder::new(BufReader::new(File::open(path)?)).map_err(tiff_error)?;
+    let mut pages = 1;
+    while decoder.more_images() {
+        decoder.next_image().map_err(tiff_error)?;
+        pages += 1;
+    }
+    Ok(pages)
+}
+
+fn tiff_error(error: TiffError) -> Error {
+    match error {
+        TiffError::IoError(error) => error,
+        error => invalid_data(error),
+    }
+}
+
+fn invalid_data<E: ToString>(error: E) -> Error {
+    Error::new(ErrorKind::InvalidData, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Grid {
+        let width = rows[0].split_whitespace().count();
+        let text = format!("{} {} 1 1 0 1\n{}\n", width, rows.len(), rows.join("\n"));
+        parse_grid(text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let grid = grid(&["0 1 2", "3 nan 5"]);
+        assert_eq!((grid.width, grid.height), (3, 2));
+        assert_eq!(grid.value(2, 1), 5.);
+        assert!(grid.value(1, 1).is_nan());
+        assert!(parse_grid("2 2 1 1 0 1\n0 1\n2\n".as_bytes()).is_err());
+        assert!(parse_grid("2 2 1 1 0 1\n0 1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn single_island() {
+        let grid = grid(&["0 0 0 0", "0 1 1 0", "0 1 1 0", "0 0 0 0"]);
+        let islands = grid.islands(0.5);
+        assert_eq!(islands.len(), 1);
+        // the boundary passes halfway between the land and water points
+        assert_eq!(islands[0].area(), 4. - 4. * 0.125);
+        assert_eq!(islands[0].bounding_box(), Some(((0.5, 0.5), (2.5, 2.5))));
+        assert!(islands[0].contains(&(1.5, 1.5)));
+    }
+
+    #[test]
+    fn islands_and_banks() {
+        let grid = grid(&[
+            "1 1 1 1 1 1 1",
+            "0 0 0 0 0 0 0",
+            "0 1 0 0 0 1 0",
+            "0 0 0 0 0 1 0",
+            "0 0 0 0 0 0 0",
+        ]);
+        let mut islands = grid.islands(0.5);
+        islands.sort_by(|a, b| a.area().partial_cmp(&b.area()).unwrap());
+        // the bank along the first row touches the border
+        assert_eq!(islands.len(), 2);
+        assert!(islands[0].contains(&(1., 2.)));
+        assert!(islands[1].contains(&(5., 2.5)));
+    }
+
+    #[test]
+    fn saddle() {
+        // diagonal land points are one island only if the centre of the square between them is land
+        let grid = grid(&["0 0 0 0", "0 1 0 0", "0 0 1 0", "0 0 0 0"]);
+        assert_eq!(grid.islands(0.5).len(), 2);
+        assert_eq!(grid.islands(0.4).len(), 1);
+        // crossings at grid points on the level are not repeated
+        let islands = grid.islands(0.);
+        assert_eq!(islands.len(), 1);
+        let vertices = islands[0].vertices();
+        assert!((0..vertices.len()).all(|i| vertices[i] != vertices[(i + 1) % vertices.len()]));
+    }
+
+    #[test]
+    fn tiff_stack() {
+        use tiff::encoder::{colortype, TiffEncoder};
+
+        let path = std::env::temp_dir().join(format!("island-tracker-stack-{}.tiff", std::process::id()));
+        let mut encoder = TiffEncoder::new(File::create(&path).unwrap()).unwrap();
+        let mut frame = vec![0f32; 5 * 4];
+        encoder.write_image::<colortype::Gray32Float>(5, 4, &frame).unwrap();
+        frame[2 * 5 + 2] = 1.;
+        frame[3] = 4.;
+        // a GeoTIFF page with pixels of 2 by 3
+        let mut image = encoder.new_image::<colortype::Gray32Float>(5, 4).unwrap();
+        image
+            .encoder()
+            .write_tag(Tag::ModelPixelScaleTag, &[2f64, 3., 0.][..])
+            .unwrap();
+        image.write_data(&frame).unwrap();
+        drop(encoder);
+
+        assert_eq!(count_tiff_pages(&path).unwrap(), 2);
+        assert_eq!(read_tiff_page(&path, 0).unwrap().cell_size, (1., 1.));
+        let mut grid = read_tiff_page(&path, 1).unwrap();
+        assert_eq!((grid.width, grid.height), (5, 4));
+        assert_eq!(grid.cell_size, (2., 3.));
+        assert_eq!(grid.value(2, 2), 1.);
+        assert_eq!(grid.islands(0.5).len(), 1);
+        grid.add(&read_tiff_page(&path, 1).unwrap()).unwrap();
+        assert_eq!(grid.value(2, 2), 2.);
+        assert!(read_tiff_page(&path, 2).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn lake() {
+        let grid = grid(&["0 0 0 0 0", "0 1 1 1 0", "0 1 0 1 0", "0 1 1 1 0", "0 0 0 0 0"]);
+        let islands = grid.islands(0.5);
+        assert_eq!(islands.len(), 1);
+        assert!(islands[0].contains(&(2., 2.)));
+    }
+}
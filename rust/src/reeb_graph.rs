@@ -1,4 +1,8 @@
-use fxhash::FxHashMap;
+use std::io::{Result, Write};
+
+use fxhash::{FxHashMap, FxHashSet};
+
+use crate::geometry::Descriptors;
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct CriticalPoint {
@@ -11,13 +15,37 @@ impl CriticalPoint {
     }
 }
 
+/// Kind of a critical point in the Reeb graph, with edges pointing forward in time
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CriticalKind {
+    /// One island in the previous layer and one island in the next layer
+    Regular,
+    /// No island in the previous layer
+    Birth,
+    /// No island in the next layer
+    Death,
+    /// Several islands in the previous layer joined
+    Merge,
+    /// The island separates into several islands in the next layer
+    Split,
+    /// Several islands joined and separate again in the next layer
+    MergeSplit,
+    /// No islands in the previous and next layer
+    Isolated,
+}
+
 #[derive(Debug)]
 pub struct ReebGraph {
     slices: FxHashMap<i32, FxHashMap<CriticalPoint, Vec<CriticalPoint>>>,
     x_coords: FxHashMap<CriticalPoint, f64>,
     indegrees: FxHashMap<CriticalPoint, i32>,
     outdegrees: FxHashMap<CriticalPoint, i32>,
-    root: CriticalPoint
+    descriptors: FxHashMap<CriticalPoint, Descriptors>,
+    /// Layer of every critical point
+    layers: FxHashMap<CriticalPoint, i32>,
+    /// Edges that bridge layers in which the island was not found
+    gaps: FxHashSet<(CriticalPoint, CriticalPoint)>,
+    root: CriticalPoint,
 }
 
 impl ReebGraph {
@@ -32,79 +60,98 @@ impl ReebGraph {
         root_map.insert(layer, edges);
         x_map.insert(root.clone(), x);
         indeg.insert(root.clone(), 0);
-        outdeg.insert(root.clone(), 1);
-        ReebGraph { slices: root_map, x_coords: x_map, indegrees: indeg, outdegrees: outdeg, root: root.clone() }
+        outdeg.insert(root.clone(), 0);
+        let mut layers = FxHashMap::default();
+        layers.insert(root.clone(), layer);
+        ReebGraph {
+            slices: root_map,
+            x_coords: x_map,
+            indegrees: indeg,
+            outdegrees: outdeg,
+            descriptors: FxHashMap::default(),
+            layers,
+            gaps: FxHashSet::default(),
+            root: root.clone(),
+        }
     }
 
-    /// Joins a parent to a new critical point `point` and creates a new entry for `point`
-    ///
-    /// # Panics
-    /// Panics when parent has not been inserted into the reeb graph before.
-    pub fn add_point(&mut self, layer: i32, parent: &CriticalPoint, point: &CriticalPoint, point_x: f64) {
-        // layer
-        match self.slices.get_mut(&layer) {
-            Some(_) => {}
-            None => {
-                let mut edges = FxHashMap::default();
-                edges.insert(parent.clone(), Vec::new());
-                self.slices.insert(layer, edges);
-            }
-        }
+    /// Returns all the critical points together with their layer
+    pub fn points(&self) -> impl Iterator<Item = (&CriticalPoint, i32)> {
+        self.layers.iter().map(|(point, layer)| (point, *layer))
+    }
 
-        // parent in layer
-        match self.slices.get_mut(&layer).unwrap().get_mut(&parent) {
-            Some(p) => {
-                p.push(point.clone());
-            }
-            None => {
-                let layer = self.slices.get_mut(&layer).unwrap();
-                layer.insert(parent.clone(), Vec::new());
-                layer.get_mut(&parent).unwrap().push(point.clone());
-            }
-        }
+    /// Attaches the shape descriptors of the island represented by `point`
+    pub fn set_descriptors(&mut self, point: &CriticalPoint, descriptors: Descriptors) {
+        self.descriptors.insert(point.clone(), descriptors);
+    }
 
-        match self.x_coords.get_mut(&point.clone()) {
-            Some(_) => {}
-            None => {
-                self.x_coords.insert(point.clone(), point_x);
-            }
-        }
+    /// Returns the shape descriptors of the island represented by `point`, if they were attached
+    pub fn descriptors(&self, point: &CriticalPoint) -> Option<&Descriptors> {
+        self.descriptors.get(point)
+    }
 
-        match self.indegrees.get_mut(&point.clone()) {
-            Some(d) => {
-                *d += 1;
-            }
-            None => {
-                self.indegrees.insert(point.clone(), 1);
-            }
-        }
+    /// Joins a parent to a new critical point `point` and creates a new entry for `point`.
+    /// The parent should have been inserted into the reeb graph before.
+    pub fn add_point(&mut self, layer: i32, parent: &CriticalPoint, point: &CriticalPoint, point_x: f64) {
+        self.add_node(point, layer + 1, point_x);
+        self.add_edge(layer, parent, point);
+    }
 
-        match self.indegrees.get_mut(&parent.clone()) {
-            Some(_) => {}
-            None => {
-                self.indegrees.insert(parent.clone(), 0);
-            }
-        }
+    /// Creates an entry for `point` in layer `layer` without joining it to other critical points,
+    /// does nothing if `point` already has an entry
+    pub fn add_node(&mut self, point: &CriticalPoint, layer: i32, point_x: f64) {
+        self.layers.entry(point.clone()).or_insert(layer);
+        self.x_coords.entry(point.clone()).or_insert(point_x);
+        self.indegrees.entry(point.clone()).or_insert(0);
+        self.outdegrees.entry(point.clone()).or_insert(0);
+    }
 
+    /// Joins `from` in layer `layer` to `to` in a later layer, edges always point forward in time.
+    /// The layer of `to` is the one it was inserted with, so layers may be skipped.
+    pub fn add_edge(&mut self, layer: i32, from: &CriticalPoint, to: &CriticalPoint) {
+        self.slices
+            .entry(layer)
+            .or_default()
+            .entry(from.clone())
+            .or_default()
+            .push(to.clone());
 
-        match self.outdegrees.get_mut(&parent.clone()) {
-            Some(d) => {
-                *d += 1;
-            }
-            None => {
-                self.outdegrees.insert(parent.clone(), 1);
-            }
-        }
+        *self.indegrees.entry(to.clone()).or_insert(0) += 1;
+        self.indegrees.entry(from.clone()).or_insert(0);
+        *self.outdegrees.entry(from.clone()).or_insert(0) += 1;
+        self.outdegrees.entry(to.clone()).or_insert(0);
+    }
 
-        match self.outdegrees.get_mut(&point.clone()) {
-            Some(_) => {}
-            None => {
-                self.outdegrees.insert(point.clone(), 0);
-            }
+    /// Joins `from` in layer `layer` to `to` with an edge that bridges one or more layers in which the island
+    /// was not found, e.g., because a layer is missing or the island was briefly submerged
+    pub fn add_gap_edge(&mut self, layer: i32, from: &CriticalPoint, to: &CriticalPoint) {
+        self.add_edge(layer, from, to);
+        self.gaps.insert((from.clone(), to.clone()));
+    }
+
+    /// Returns whether the edge from `from` to `to` bridges a gap
+    pub fn is_gap(&self, from: &CriticalPoint, to: &CriticalPoint) -> bool {
+        self.gaps.contains(&(from.clone(), to.clone()))
+    }
+
+    /// Classifies `point` by the number of edges entering it from the previous layer and leaving it to the next layer.
+    /// Merges and splits take precedence over births and deaths.
+    pub fn classify(&self, point: &CriticalPoint) -> CriticalKind {
+        let indegree = *self.indegrees.get(point).unwrap_or(&0);
+        let outdegree = *self.outdegrees.get(point).unwrap_or(&0);
+        match (indegree, outdegree) {
+            (0, 0) => CriticalKind::Isolated,
+            (i, o) if i > 1 && o > 1 => CriticalKind::MergeSplit,
+            (i, _) if i > 1 => CriticalKind::Merge,
+            (_, o) if o > 1 => CriticalKind::Split,
+            (0, _) => CriticalKind::Birth,
+            (_, 0) => CriticalKind::Death,
+            _ => CriticalKind::Regular,
         }
     }
 
-    pub fn to_ipe(&mut self) {
+    /// Writes the Reeb graph as Ipe paths, critical points other than regular points are marked
+    pub fn write_ipe<W: Write>(&self, out: &mut W) -> Result<()> {
         const X_SCALE: f64 = 16.;
         const Y_SCALE: i32 = 16;
         for (layer, edges) in &self.slices {
@@ -113,19 +160,34 @@ impl ReebGraph {
                 let parent_x = self.x_coords.get(parent).unwrap() * X_SCALE;
                 for child in children {
                     let child_x = self.x_coords.get(child).unwrap() * X_SCALE;
-                    println!("<path layer=\"alpha\" stroke=\"black\">");
-                    println!("{} {} m", parent_x, y);
-                    println!("{} {} l", child_x, y + Y_SCALE);
-                    println!("h");
-                    println!("</path>");
-                    if *self.indegrees.get(child).unwrap() != 1 || *self.outdegrees.get(child).unwrap() != 1 {
-                        println!("<use name=\"mark/disk(sx)\" pos=\"{} {}\" size=\"normal\" stroke=\"black\"/>", child_x, y + Y_SCALE);
+                    // the child is in the next compared layer, which need not be `layer + 1`
+                    let child_y = self.layers.get(child).unwrap() * Y_SCALE;
+                    if self.is_gap(parent, child) {
+                        writeln!(out, "<path layer=\"alpha\" stroke=\"black\" dash=\"dashed\">")?;
+                    } else {
+                        writeln!(out, "<path layer=\"alpha\" stroke=\"black\">")?;
+                    }
+                    writeln!(out, "{} {} m", parent_x, y)?;
+                    writeln!(out, "{} {} l", child_x, child_y)?;
+                    writeln!(out, "h")?;
+                    writeln!(out, "</path>")?;
+                    if self.classify(child) != CriticalKind::Regular {
+                        writeln!(
+                            out,
+                            "<use name=\"mark/disk(sx)\" pos=\"{} {}\" size=\"normal\" stroke=\"black\"/>",
+                            child_x, child_y
+                        )?;
                     }
                 }
                 if parent.value == self.root.value {
-                    println!("<use name=\"mark/disk(sx)\" pos=\"{} {}\" size=\"normal\" stroke=\"black\"/>", parent_x, y);
+                    writeln!(
+                        out,
+                        "<use name=\"mark/disk(sx)\" pos=\"{} {}\" size=\"normal\" stroke=\"black\"/>",
+                        parent_x, y
+                    )?;
                 }
             }
         }
+        Ok(())
     }
-}
\ No newline at end of file
+}
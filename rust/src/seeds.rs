@@ -0,0 +1,371 @@
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::csv::split_csv;
+use crate::geometry::Polygon;
+
+/// A named region in the island to track
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seed {
+    pub name: String,
+    pub region: Region,
+    /// Layer at which to start looking for the island, the start time of the run if `None`
+    pub start_time: Option<usize>,
+}
+
+/// Where to look for the island to track
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    Point((f64, f64)),
+    /// A polygon given by its vertices, e.g. a bounding box
+    Polygon(Vec<(f64, f64)>),
+}
+
+/// Why an island was chosen for a region
+#[derive(Debug, Clone, PartialEq)]
+pub enum Choice {
+    /// The island contains the point
+    Contains,
+    /// The island overlaps the polygon, covering `fraction` of it
+    Overlap { area: f64, fraction: f64 },
+    /// No island contains or overlaps the region, this is the nearest island
+    Nearest { distance: f64 },
+}
+
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Choice::Contains => write!(f, "it contains the seed point"),
+            Choice::Overlap { area, fraction } => write!(
+                f,
+                "it overlaps the seed polygon ({} or {:.1}% of the seed)",
+                area,
+                fraction * 100.
+            ),
+            Choice::Nearest { distance } => write!(f, "it is the nearest island, at distance {}", distance),
+        }
+    }
+}
+
+/// Distances that differ by less than this fraction of the distance (or of 1 for short distances) are tied
+const TIE_TOLERANCE: f64 = 1e-9;
+
+impl Region {
+    /// Returns the box with corners `min` and `max`
+    pub fn bounding_box(min: (f64, f64), max: (f64, f64)) -> Region {
+        Region::Polygon(vec![min, (max.0, min.1), max, (min.0, max.1)])
+    }
+
+    /// Returns a representative point of the region, the point itself or the centroid of the polygon
+    pub fn anchor(&self) -> (f64, f64) {
+        match self {
+            Region::Point(point) => *point,
+            Region::Polygon(vertices) => {
+                let n = vertices.len() as f64;
+                Polygon::new(vertices.clone()).centroid().unwrap_or_else(|| {
+                    let (x, y) = vertices.iter().fold((0., 0.), |sum, v| (sum.0 + v.0, sum.1 + v.1));
+                    (x / n, y / n)
+                })
+            }
+        }
+    }
+
+    /// Chooses the islands among `islands` that the region refers to: the island containing the point, or every
+    /// island overlapping the polygon, the largest overlap first. If there is no such island, the nearest island is
+    /// chosen if it is at most `tolerance` away, together with the islands tied with it. Returns an empty list if no
+    /// island is chosen.
+    pub fn choose(&self, islands: &[Polygon], tolerance: f64) -> Vec<(usize, Choice)> {
+        let nearest = |distances: Vec<f64>| {
+            let nearest = distances
+                .iter()
+                .copied()
+                .filter(|distance| *distance <= tolerance)
+                .min_by(|a, b| a.partial_cmp(b).unwrap());
+            match nearest {
+                Some(nearest) => distances
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, distance)| *distance - nearest <= TIE_TOLERANCE * nearest.max(1.))
+                    .map(|(index, distance)| (index, Choice::Nearest { distance }))
+                    .collect(),
+                None => Vec::new(),
+            }
+        };
+
+        match self {
+            Region::Point(point) => match islands.iter().position(|island| island.contains(point)) {
+                Some(index) => vec![(index, Choice::Contains)],
+                None => nearest(islands.iter().map(|island| island.distance_to_point(point)).collect()),
+            },
+            Region::Polygon(vertices) => {
+                let seed = Polygon::new(vertices.clone());
+                let mut overlaps: Vec<_> = islands
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, island)| island.bounding_boxes_intersect(&seed))
+                    .map(|(index, island)| (index, island.overlap_area(&seed)))
+                    .filter(|(_, area)| *area > 0.)
+                    .collect();
+                if overlaps.is_empty() {
+                    return nearest(islands.iter().map(|island| island.distance(&seed)).collect());
+                }
+                // largest overlap first, the sort is stable so ties keep the order of the islands
+                overlaps.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+                overlaps
+                    .into_iter()
+                    .map(|(index, area)| {
+                        let fraction = area / seed.area();
+                        (index, Choice::Overlap { area, fraction })
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Reads the seeds in the file at `path`, a GeoJSON file if its extension is `geojson` or `json` and a CSV file
+/// otherwise
+pub fn read_seeds(path: &Path) -> Result<Vec<Seed>> {
+    let text = fs::read_to_string(path)?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("geojson") | Some("json") => parse_geojson(&text),
+        _ => parse_csv(&text),
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Parses seeds from CSV with a header containing the columns `x` and `y`, or the box columns `xmin`, `ymin`, `xmax`
+/// and `ymax`, and optionally `name` and `start_time`. Rows with a box are seeded by the box, other rows by the point.
+/// Seeds without a name are named after their row, an empty start time means the start time of the run.
+pub fn parse_csv(text: &str) -> Result<Vec<Seed>> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<String> = match lines.next() {
+        Some(header) => split_csv(header),
+        None => return Ok(Vec::new()),
+    };
+    let column = |name: &str| header.iter().position(|column| column.eq_ignore_ascii_case(name));
+    let point = column("x").zip(column("y"));
+    let bounds = match (column("xmin"), column("ymin"), column("xmax"), column("ymax")) {
+        (Some(xmin), Some(ymin), Some(xmax), Some(ymax)) => Some([xmin, ymin, xmax, ymax]),
+        _ => None,
+    };
+    if point.is_none() && bounds.is_none() {
+        return Err(invalid_data(
+            "the seed file should have the columns x and y, or xmin, ymin, xmax and ymax".to_string(),
+        ));
+    }
+    let (name, start_time) = (column("name"), column("start_time"));
+
+    let mut seeds = Vec::new();
+    for (row, line) in lines.enumerate() {
+        let fields = split_csv(line);
+        let field = |index: usize| fields.get(index).map(String::as_str).unwrap_or("");
+        let number = |index: usize| {
+            field(index)
+                .parse::<f64>()
+                .map_err(|e| invalid_data(format!("seed {}: invalid coordinate {:?}: {}", row, field(index), e)))
+        };
+        let start_time = match start_time.map(field) {
+            None | Some("") => None,
+            Some(value) => Some(
+                value
+                    .parse()
+                    .map_err(|e| invalid_data(format!("seed {}: invalid start time {:?}: {}", row, value, e)))?,
+            ),
+        };
+        let region = match bounds {
+            Some([xmin, ymin, xmax, ymax]) if bounds.iter().flatten().any(|column| !field(*column).is_empty()) => {
+                Region::bounding_box((number(xmin)?, number(ymin)?), (number(xmax)?, number(ymax)?))
+            }
+            _ => match point {
+                Some((x, y)) => Region::Point((number(x)?, number(y)?)),
+                None => return Err(invalid_data(format!("seed {}: the box is empty", row))),
+            },
+        };
+        seeds.push(Seed {
+            name: match name.map(field) {
+                None | Some("") => format!("seed-{}", row),
+                Some(name) => name.to_string(),
+            },
+            region,
+            start_time,
+        });
+    }
+    Ok(seeds)
+}
+
+/// Parses seeds from a GeoJSON feature collection (or a single feature) of points and polygons,
+/// only the outer ring of a polygon is used.
+/// The optional properties `name` and `start_time` give the name and start time of a seed.
+pub fn parse_geojson(text: &str) -> Result<Vec<Seed>> {
+    let json: Value = serde_json::from_str(text).map_err(|e| invalid_data(e.to_string()))?;
+    let features = match json["type"].as_str() {
+        Some("FeatureCollection") => json["features"]
+            .as_array()
+            .ok_or_else(|| invalid_data("the feature collection has no features".to_string()))?
+            .iter()
+            .collect(),
+        Some("Feature") => vec![&json],
+        _ => {
+            return Err(invalid_data(
+                "expected a GeoJSON Feature or FeatureCollection".to_string(),
+            ))
+        }
+    };
+
+    let mut seeds = Vec::new();
+    for (index, feature) in features.into_iter().enumerate() {
+        let geometry = &feature["geometry"];
+        let coordinates = &geometry["coordinates"];
+        let position = |position: &Value| match (position[0].as_f64(), position[1].as_f64()) {
+            (Some(x), Some(y)) => Ok((x, y)),
+            _ => Err(invalid_data(format!(
+                "seed {}: invalid coordinates {}",
+                index, position
+            ))),
+        };
+        let region = match geometry["type"].as_str() {
+            Some("Point") => Region::Point(position(coordinates)?),
+            Some("Polygon") => {
+                let ring = coordinates[0]
+                    .as_array()
+                    .ok_or_else(|| invalid_data(format!("seed {}: the polygon has no outer ring", index)))?;
+                let mut vertices = ring.iter().map(position).collect::<Result<Vec<_>>>()?;
+                // GeoJSON rings repeat their first position at the end
+                if vertices.len() > 1 && vertices.first() == vertices.last() {
+                    vertices.pop();
+                }
+                Region::Polygon(vertices)
+            }
+            _ => {
+                return Err(invalid_data(format!(
+                    "seed {}: only Point and Polygon geometries are supported",
+                    index
+                )))
+            }
+        };
+
+        let properties = &feature["properties"];
+        let name = match &properties["name"] {
+            Value::String(name) => name.clone(),
+            Value::Null => format!("seed-{}", index),
+            name => name.to_string(),
+        };
+        let start_time = match &properties["start_time"] {
+            Value::Null => None,
+            value => Some(
+                value
+                    .as_u64()
+                    .ok_or_else(|| invalid_data(format!("seed {}: invalid start time {}", index, value)))?
+                    as usize,
+            ),
+        };
+        seeds.push(Seed {
+            name,
+            region,
+            start_time,
+        });
+    }
+    Ok(seeds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_seeds() {
+        let seeds = parse_csv("name,x,y,start_time\n\"Island, north\",1.5,2,\n,3,4,7\n").unwrap();
+        assert_eq!(
+            seeds,
+            vec![
+                Seed {
+                    name: "Island, north".to_string(),
+                    region: Region::Point((1.5, 2.)),
+                    start_time: None,
+                },
+                Seed {
+                    name: "seed-1".to_string(),
+                    region: Region::Point((3., 4.)),
+                    start_time: Some(7),
+                },
+            ]
+        );
+        assert!(parse_csv("name,x\na,1\n").is_err());
+        let seeds = parse_csv("x,y,xmin,ymin,xmax,ymax\n1,2,,,,\n,,0,0,2,1\n").unwrap();
+        assert_eq!(seeds[0].region, Region::Point((1., 2.)));
+        assert_eq!(seeds[1].region, Region::bounding_box((0., 0.), (2., 1.)));
+        assert!(parse_csv("x,y\n1,a\n").is_err());
+    }
+
+    #[test]
+    fn geojson_seeds() {
+        let text = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [1.5, 2]}, "properties": {"name": "a", "start_time": 3}},
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [3, 4]}, "properties": null},
+            {"type": "Feature", "geometry": {"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [0, 1], [0, 0]]]}}
+        ]}"#;
+        let seeds = parse_geojson(text).unwrap();
+        assert_eq!(seeds.len(), 3);
+        assert_eq!(seeds[0].name, "a");
+        assert_eq!(seeds[0].start_time, Some(3));
+        assert_eq!(seeds[1].name, "seed-1");
+        assert_eq!(seeds[1].region, Region::Point((3., 4.)));
+        assert_eq!(seeds[2].region, Region::Polygon(vec![(0., 0.), (1., 0.), (0., 1.)]));
+    }
+
+    fn square(x: f64, y: f64, size: f64) -> Polygon {
+        Polygon::new(vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)])
+    }
+
+    #[test]
+    fn choose_islands() {
+        let islands = vec![square(0., 0., 2.), square(3., 0., 2.)];
+        assert_eq!(
+            Region::Point((1., 1.)).choose(&islands, 0.),
+            vec![(0, Choice::Contains)]
+        );
+        // the point lies in the channel between the islands
+        assert_eq!(Region::Point((2.6, 1.)).choose(&islands, 0.), vec![]);
+        let chosen = Region::Point((2.6, 1.)).choose(&islands, 1.);
+        assert_eq!(chosen.len(), 1);
+        assert!(matches!(chosen[0], (1, Choice::Nearest { distance }) if (distance - 0.4).abs() < 1e-9));
+        // a point on the boundary is at distance zero
+        assert_eq!(
+            Region::Point((2., 1.)).choose(&islands, 0.),
+            vec![(0, Choice::Nearest { distance: 0. })]
+        );
+        // a point halfway between the islands, up to rounding
+        let chosen = Region::Point((2.5 + 1e-12, 1.)).choose(&islands, 1.);
+        assert_eq!(chosen.iter().map(|(index, _)| *index).collect::<Vec<_>>(), vec![0, 1]);
+
+        let seed = Region::bounding_box((1., 0.), (4.5, 1.));
+        assert_eq!(
+            seed.choose(&islands, 0.),
+            vec![
+                (
+                    1,
+                    Choice::Overlap {
+                        area: 1.5,
+                        fraction: 1.5 / 3.5
+                    }
+                ),
+                (
+                    0,
+                    Choice::Overlap {
+                        area: 1.,
+                        fraction: 1. / 3.5
+                    }
+                ),
+            ]
+        );
+        assert_eq!(Region::bounding_box((2.2, 0.), (2.8, 1.)).choose(&islands, 0.), vec![]);
+    }
+}
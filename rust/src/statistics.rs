@@ -0,0 +1,347 @@
+use std::io::{Result, Write};
+
+use rand::Rng;
+use serde_json::json;
+
+use crate::csv::field;
+use crate::geometry::Polygon;
+use crate::graph::Cleaning;
+use crate::io;
+use crate::io::{ArchiveReader, Input};
+
+/// Statistics of a single island in a layer
+pub struct IslandStatistics {
+    pub layer: usize,
+    pub index: usize,
+    pub area: f64,
+    pub perimeter: f64,
+    pub centroid: (f64, f64),
+    pub disk_center: (f64, f64),
+    pub disk_radius: f64,
+    pub vertices: usize,
+    pub min_weight: Option<f64>,
+}
+
+impl IslandStatistics {
+    /// Computes the statistics of all the islands of a layer, `rng` is used for the smallest enclosing disks
+    pub fn from_layer<R: Rng + ?Sized>(layer: usize, islands: &[Polygon], rng: &mut R) -> Vec<IslandStatistics> {
+        islands
+            .iter()
+            .enumerate()
+            .map(|(index, island)| {
+                let disk = island.smallest_disk(rng);
+                IslandStatistics {
+                    layer,
+                    index,
+                    area: island.area(),
+                    perimeter: island.perimeter(),
+                    centroid: island.centroid().unwrap_or((f64::NAN, f64::NAN)),
+                    disk_center: disk.as_ref().map_or((f64::NAN, f64::NAN), |d| d.centroid()),
+                    disk_radius: disk.as_ref().map_or(f64::NAN, |d| d.radius()),
+                    vertices: island.vertices().len(),
+                    min_weight: island.min_boundary_weight(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn write_csv_header<W: Write>(out: &mut W) -> Result<()> {
+        writeln!(
+            out,
+            "layer,island,area,perimeter,centroid_x,centroid_y,disk_x,disk_y,disk_radius,vertices,min_weight"
+        )
+    }
+
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> Result<()> {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.layer,
+            self.index,
+            field(self.area),
+            field(self.perimeter),
+            field(self.centroid.0),
+            field(self.centroid.1),
+            field(self.disk_center.0),
+            field(self.disk_center.1),
+            field(self.disk_radius),
+            self.vertices,
+            field(self.min_weight.unwrap_or(f64::NAN)),
+        )
+    }
+}
+
+/// The bounds of the bins of the area histogram, a bin per decade, below the first bound and from the last bound on
+pub const AREA_BINS: [f64; 7] = [1., 10., 100., 1e3, 1e4, 1e5, 1e6];
+
+/// Aggregated statistics of all the islands in a layer
+pub struct LayerStatistics {
+    pub layer: usize,
+    pub count: usize,
+    pub total_area: f64,
+    pub mean_area: f64,
+    /// Minimum, first quartile, median, third quartile and maximum of the island areas
+    pub quartiles: [f64; 5],
+    /// Number of islands with an area below the first bound of [`AREA_BINS`], between each two consecutive bounds,
+    /// and from the last bound on
+    pub histogram: [usize; AREA_BINS.len() + 1],
+}
+
+impl LayerStatistics {
+    pub fn from_islands(layer: usize, islands: &[IslandStatistics]) -> LayerStatistics {
+        let mut areas: Vec<_> = islands.iter().map(|island| island.area).collect();
+        areas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let count = areas.len();
+        let total_area: f64 = areas.iter().sum();
+        let mut quartiles = [f64::NAN; 5];
+        for (i, quartile) in quartiles.iter_mut().enumerate() {
+            *quartile = quantile(&areas, i as f64 / 4.);
+        }
+        let mut histogram = [0; AREA_BINS.len() + 1];
+        for &area in &areas {
+            histogram[AREA_BINS.iter().take_while(|&&bound| bound <= area).count()] += 1;
+        }
+
+        LayerStatistics {
+            layer,
+            count,
+            total_area,
+            mean_area: if count == 0 {
+                f64::NAN
+            } else {
+                total_area / count as f64
+            },
+            quartiles,
+            histogram,
+        }
+    }
+
+    pub fn median_area(&self) -> f64 {
+        self.quartiles[2]
+    }
+
+    /// Writes the header, the histogram columns are named by their bounds, e.g. `area_10_100`
+    pub fn write_csv_header<W: Write>(out: &mut W) -> Result<()> {
+        write!(
+            out,
+            "layer,count,total_area,mean_area,median_area,min_area,q1_area,q3_area,max_area"
+        )?;
+        write!(out, ",area_below_{}", AREA_BINS[0])?;
+        for bounds in AREA_BINS.windows(2) {
+            write!(out, ",area_{}_{}", bounds[0], bounds[1])?;
+        }
+        writeln!(out, ",area_from_{}", AREA_BINS[AREA_BINS.len() - 1])
+    }
+
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> Result<()> {
+        write!(
+            out,
+            "{},{},{},{},{},{},{},{},{}",
+            self.layer,
+            self.count,
+            field(self.total_area),
+            field(self.mean_area),
+            field(self.median_area()),
+            field(self.quartiles[0]),
+            field(self.quartiles[1]),
+            field(self.quartiles[3]),
+            field(self.quartiles[4]),
+        )?;
+        for count in &self.histogram {
+            write!(out, ",{}", count)?;
+        }
+        writeln!(out)
+    }
+}
+
+/// Writes the statistics of every island in the layers `times` of `inputs` to `islands_out`, and the aggregated
+/// statistics per layer to `layers_out`. The layers are read with [`io::read_islands`], `rng` is used for the smallest
+/// enclosing disks.
+#[allow(clippy::too_many_arguments)]
+pub fn write_statistics<R: Rng + ?Sized, W: Write, V: Write>(
+    inputs: &[Input],
+    times: &[usize],
+    delta: f64,
+    level: f64,
+    cleaning: &Cleaning,
+    islands_out: &mut W,
+    mut layers_out: Option<&mut V>,
+    rng: &mut R,
+) -> Result<()> {
+    IslandStatistics::write_csv_header(islands_out)?;
+    if let Some(out) = layers_out.as_mut() {
+        LayerStatistics::write_csv_header(out)?;
+    }
+
+    let mut archive = ArchiveReader::for_inputs(inputs);
+    for &layer in times {
+        let islands = io::read_islands(&inputs[layer], delta, level, cleaning, archive.as_mut())?;
+        let statistics = IslandStatistics::from_layer(layer, &islands, rng);
+        for island in &statistics {
+            island.write_csv(islands_out)?;
+        }
+        if let Some(out) = layers_out.as_mut() {
+            LayerStatistics::from_islands(layer, &statistics).write_csv(out)?;
+        }
+    }
+
+    islands_out.flush()?;
+    if let Some(out) = layers_out {
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// Writes the number of islands of every layer, given as (layer, islands) pairs, as text
+pub fn write_counts_text<W: Write>(counts: &[(usize, usize)], out: &mut W) -> Result<()> {
+    writeln!(out, "t,\t islands")?;
+    for (layer, islands) in counts {
+        writeln!(out, "{},\t {}", layer, islands)?;
+    }
+    Ok(())
+}
+
+/// Writes the number of islands of every layer, given as (layer, islands) pairs, as CSV
+pub fn write_counts_csv<W: Write>(counts: &[(usize, usize)], out: &mut W) -> Result<()> {
+    writeln!(out, "layer,islands")?;
+    for (layer, islands) in counts {
+        writeln!(out, "{},{}", layer, islands)?;
+    }
+    Ok(())
+}
+
+/// Writes the number of islands of every layer, given as (layer, islands) pairs, as a JSON array of objects
+pub fn write_counts_json<W: Write>(counts: &[(usize, usize)], out: &mut W) -> Result<()> {
+    let counts: Vec<_> = counts
+        .iter()
+        .map(|(layer, islands)| json!({"layer": layer, "islands": islands}))
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &counts)?;
+    writeln!(out)
+}
+
+/// The persistence of an island, the smallest weight of the channels around it
+pub struct IslandPersistence {
+    pub layer: usize,
+    pub index: usize,
+    pub area: f64,
+    /// `None` if the weights of the channels are unknown
+    pub persistence: Option<f64>,
+}
+
+impl IslandPersistence {
+    pub fn from_layer(layer: usize, islands: &[Polygon]) -> Vec<IslandPersistence> {
+        islands
+            .iter()
+            .enumerate()
+            .map(|(index, island)| IslandPersistence {
+                layer,
+                index,
+                area: island.area(),
+                persistence: island.min_boundary_weight(),
+            })
+            .collect()
+    }
+}
+
+/// Writes the persistence of the islands as CSV, unknown persistences are left empty
+pub fn write_persistence_csv<W: Write>(rows: &[IslandPersistence], out: &mut W) -> Result<()> {
+    writeln!(out, "layer,island,area,persistence")?;
+    for row in rows {
+        let persistence = row.persistence.map(|weight| weight.to_string()).unwrap_or_default();
+        writeln!(out, "{},{},{},{}", row.layer, row.index, row.area, persistence)?;
+    }
+    Ok(())
+}
+
+/// Writes the persistence of the islands as a JSON array of objects, unknown persistences are `null`
+pub fn write_persistence_json<W: Write>(rows: &[IslandPersistence], out: &mut W) -> Result<()> {
+    let rows: Vec<_> = rows
+        .iter()
+        .map(|row| json!({"layer": row.layer, "island": row.index, "area": row.area, "persistence": row.persistence}))
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &rows)?;
+    writeln!(out)
+}
+
+/// Returns the `q`-quantile of the sorted values `sorted`, interpolating linearly between values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+
+    let position = q * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    let fraction = position - below as f64;
+    sorted[below] + (sorted[above] - sorted[below]) * fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn quantiles() {
+        assert_eq!(quantile(&[1., 2., 3., 4.], 0.5), 2.5);
+        assert_eq!(quantile(&[1., 2., 3., 4., 5.], 0.25), 2.);
+        assert!(quantile(&[], 0.5).is_nan());
+    }
+
+    #[test]
+    fn layer_statistics() {
+        let islands = vec![
+            Polygon::new(vec![(0., 0.), (1., 0.), (1., 1.), (0., 1.)]),
+            Polygon::new(vec![(2., 0.), (4., 0.), (4., 2.), (2., 2.)]),
+            Polygon::new(vec![(5., 0.), (8., 0.), (8., 3.), (5., 3.)]),
+        ];
+        let mut rng = StdRng::seed_from_u64(0);
+        let statistics = IslandStatistics::from_layer(7, &islands, &mut rng);
+        let layer = LayerStatistics::from_islands(7, &statistics);
+        assert_eq!(layer.count, 3);
+        assert_eq!(layer.total_area, 14.);
+        assert_eq!(layer.median_area(), 4.);
+        assert_eq!(layer.quartiles[4], 9.);
+        assert_eq!(layer.histogram, [0, 3, 0, 0, 0, 0, 0, 0]);
+
+        let mut out = Vec::new();
+        LayerStatistics::write_csv_header(&mut out).unwrap();
+        layer.write_csv(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let (header, row) = text.split_once('\n').unwrap();
+        let bins: Vec<_> = header.split(',').skip(9).collect();
+        assert_eq!(bins.len(), 8);
+        assert_eq!(bins[..3], ["area_below_1", "area_1_10", "area_10_100"]);
+        assert_eq!(bins[6..], ["area_100000_1000000", "area_from_1000000"]);
+        assert_eq!(row, "7,3,14,4.666666666666667,4,1,2.5,6.5,9,0,3,0,0,0,0,0,0\n");
+
+        let mut out = Vec::new();
+        statistics[0].write_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "7,0,1,4,0.5,0.5,0.5,0.5,0.7071067811865476,4,\n"
+        );
+    }
+
+    #[test]
+    fn persistence() {
+        let square = || Polygon::new(vec![(0., 0.), (2., 0.), (2., 2.), (0., 2.)]);
+        let mut rows = IslandPersistence::from_layer(4, &[square()]);
+        rows.extend(IslandPersistence::from_layer(5, &[square().with_boundary_weight(1.5)]));
+
+        let mut out = Vec::new();
+        write_persistence_csv(&rows, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "layer,island,area,persistence\n4,0,4,\n5,0,4,1.5\n"
+        );
+        let mut out = Vec::new();
+        write_persistence_json(&rows, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["persistence"], json!(null));
+        assert_eq!(json[1]["persistence"], json!(1.5));
+    }
+}
@@ -0,0 +1,1002 @@
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Instant;
+
+use fxhash::FxHashMap;
+use log::{info, warn};
+use rand::Rng;
+
+use crate::assignment::assign;
+use crate::geometry::Polygon;
+use crate::graph::Cleaning;
+use crate::io;
+use crate::io::{ArchiveReader, Input};
+use crate::reeb_graph::{CriticalPoint, ReebGraph};
+use crate::seeds::{Choice, Region, Seed};
+
+/// Direction in time in which an island is tracked from the start layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+    Both,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forward" => Ok(Direction::Forward),
+            "backward" => Ok(Direction::Backward),
+            "both" => Ok(Direction::Both),
+            _ => Err(format!("unknown direction {}, expected forward, backward or both", s)),
+        }
+    }
+}
+
+/// Kind of matching between the islands of two compared layers, see [`Matching`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchingKind {
+    Containment,
+    Optimal,
+}
+
+impl FromStr for MatchingKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "containment" => Ok(MatchingKind::Containment),
+            "optimal" => Ok(MatchingKind::Optimal),
+            _ => Err(format!("unknown matching {}, expected containment or optimal", s)),
+        }
+    }
+}
+
+/// How the islands of two compared layers are matched
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Matching {
+    /// Islands match if either contains the centroid of the other
+    Containment,
+    /// Islands are matched by a global optimal assignment, see [`assign`]
+    Optimal { max_cost: f64, split_penalty: f64 },
+}
+
+impl Matching {
+    /// Returns the matching of `kind`, the parameters are only used by the optimal matching
+    pub fn new(kind: MatchingKind, max_cost: f64, split_penalty: f64) -> Matching {
+        match kind {
+            MatchingKind::Containment => Matching::Containment,
+            MatchingKind::Optimal => Matching::Optimal {
+                max_cost,
+                split_penalty,
+            },
+        }
+    }
+
+    /// Returns for every island of `from` the islands of `to` it matches
+    fn matches(&self, from: &Layer, to: &Layer) -> Vec<Vec<usize>> {
+        let mut matches = vec![Vec::new(); from.islands.len()];
+        match *self {
+            Matching::Containment => {
+                for (i, matched) in matches.iter_mut().enumerate() {
+                    matched.extend((0..to.islands.len()).filter(|j| from.matches(i, to, *j)));
+                }
+            }
+            Matching::Optimal {
+                max_cost,
+                split_penalty,
+            } => {
+                let pairs = assign(
+                    &from.islands,
+                    &from.centroids,
+                    &to.islands,
+                    &to.centroids,
+                    max_cost,
+                    split_penalty,
+                );
+                for (i, j) in pairs {
+                    matches[i].push(j);
+                }
+            }
+        }
+        matches
+    }
+}
+
+/// The point of an island that is used to match it with the islands of other layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// The polygonal centroid
+    Centroid,
+    /// The center of the smallest enclosing disk
+    Disk,
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "centroid" => Ok(Method::Centroid),
+            "disk" => Ok(Method::Disk),
+            _ => Err(format!("unknown method {}, expected centroid or disk", s)),
+        }
+    }
+}
+
+/// Parameters for tracking islands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackingOptions {
+    /// Distance used when reading the networks, see [`io::read_network`]
+    pub delta: f64,
+    /// Water level above which the cells of raster layers are land, see [`io::read_islands`]
+    pub level: f64,
+    /// How the networks are cleaned before their islands are extracted
+    pub cleaning: Cleaning,
+    pub method: Method,
+    pub matching: Matching,
+    pub direction: Direction,
+    /// Number of consecutive compared layers in which a tracked island may be missing before it is considered dead
+    pub max_gap: usize,
+    /// Largest distance to the nearest island when no island contains or overlaps the seed, see [`Region::choose`]
+    pub tolerance: f64,
+}
+
+impl Default for TrackingOptions {
+    fn default() -> Self {
+        TrackingOptions {
+            delta: 1e2,
+            level: 0.,
+            cleaning: Cleaning::default(),
+            method: Method::Centroid,
+            matching: Matching::Containment,
+            direction: Direction::Forward,
+            max_gap: 0,
+            tolerance: 0.,
+        }
+    }
+}
+
+/// Reasons why an island could not be tracked
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackingError {
+    /// None of the compared layers is at or after the start time (at or before when tracking backward)
+    StartTimeOutside { start_time: usize },
+    /// No island was found for the seed in any of the searched layers
+    NotFound,
+}
+
+impl fmt::Display for TrackingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrackingError::StartTimeOutside { start_time } => {
+                write!(f, "start time {} is outside the compared layers", start_time)
+            }
+            TrackingError::NotFound => write!(f, "no island found for the seed"),
+        }
+    }
+}
+
+impl Error for TrackingError {}
+
+/// An island tracked over time
+pub struct Tracked {
+    /// Names of the seeds that lie in the island, empty if the island was not tracked from seeds
+    pub names: Vec<String>,
+    pub reeb: ReebGraph,
+    /// Layer of the island at which tracking started
+    pub start_layer: usize,
+    /// Index of the island at which tracking started among the islands of its layer
+    pub start_island: usize,
+    /// Layer and index within the layer of every island in the Reeb graph, sorted
+    pub islands: Vec<(usize, usize)>,
+    /// Why the island was chosen for the seed
+    pub choice: Choice,
+    /// The layers that could not be read and were skipped, together with the error
+    pub skipped: Vec<(usize, std::io::Error)>,
+}
+
+/// The islands tracked from a list of seeds
+pub struct TrackedSeeds {
+    pub tracked: Vec<Tracked>,
+    /// The names of the seeds for which no island was tracked, together with the reason
+    pub failed: Vec<(String, TrackingError)>,
+}
+
+/// The islands of a layer together with the points used to match them
+struct Layer {
+    /// Index of the layer in the inputs
+    index: usize,
+    islands: Vec<Polygon>,
+    /// Centroid of every island, computed with the tracking method
+    centroids: Vec<(f64, f64)>,
+}
+
+impl Layer {
+    fn load<R: Rng + ?Sized>(
+        inputs: &[Input],
+        options: &TrackingOptions,
+        index: usize,
+        archive: Option<&mut ArchiveReader>,
+        rng: &mut R,
+    ) -> std::io::Result<Layer> {
+        let islands = io::read_islands(&inputs[index], options.delta, options.level, &options.cleaning, archive)?;
+        Ok(Layer::new(index, islands, options.method, rng))
+    }
+
+    fn new<R: Rng + ?Sized>(index: usize, islands: Vec<Polygon>, method: Method, rng: &mut R) -> Layer {
+        let centroids = islands
+            .iter()
+            .map(|island| match method {
+                Method::Centroid => island.centroid().unwrap(),
+                Method::Disk => island.smallest_disk_centroid(rng).unwrap(),
+            })
+            .collect();
+        Layer {
+            index,
+            islands,
+            centroids,
+        }
+    }
+
+    /// Returns whether island `i` of `self` and island `j` of `other` are the same island
+    fn matches(&self, i: usize, other: &Layer, j: usize) -> bool {
+        // if so: split or normal, or merge or normal
+        self.islands[i].contains(&other.centroids[j]) || other.islands[j].contains(&self.centroids[i])
+    }
+}
+
+/// Loads the layers while tracking. If `shared` is set, the layers loaded while searching for the start islands are
+/// kept for the other seeds until they are released. The members of an archive are read with a reader owned by the
+/// layers, so its pass over the archive ends when tracking ends.
+struct Layers<'a> {
+    inputs: &'a [Input],
+    options: TrackingOptions,
+    shared: bool,
+    loaded: FxHashMap<usize, Rc<Layer>>,
+    archive: Option<ArchiveReader>,
+}
+
+impl<'a> Layers<'a> {
+    fn new(inputs: &'a [Input], options: &TrackingOptions, shared: bool) -> Layers<'a> {
+        Layers {
+            inputs,
+            options: *options,
+            shared,
+            loaded: FxHashMap::default(),
+            archive: ArchiveReader::for_inputs(inputs),
+        }
+    }
+
+    fn get<R: Rng + ?Sized>(&mut self, index: usize, rng: &mut R) -> std::io::Result<Rc<Layer>> {
+        if let Some(layer) = self.loaded.get(&index) {
+            return Ok(layer.clone());
+        }
+        let layer = Rc::new(Layer::load(
+            self.inputs,
+            &self.options,
+            index,
+            self.archive.as_mut(),
+            rng,
+        )?);
+        if self.shared {
+            self.loaded.insert(index, layer.clone());
+        }
+        Ok(layer)
+    }
+
+    /// Drops the kept layers and stops keeping new ones, the sweeps load every layer once for all islands
+    fn release(&mut self) {
+        self.shared = false;
+        self.loaded.clear();
+    }
+}
+
+/// Islands still being tracked by a sweep, together with their layer and its position in the compared layers
+type Frontier = Vec<(Rc<Layer>, usize, Vec<usize>)>;
+
+/// Builds the Reeb graph while sweeping over the layers
+struct Tracker {
+    reeb: ReebGraph,
+    /// Critical point id of every (layer, island) pair inserted so far
+    ids: FxHashMap<(usize, usize), usize>,
+    /// The layers that could not be read
+    skipped: Vec<(usize, std::io::Error)>,
+}
+
+impl Tracker {
+    /// Returns the critical point of island `index` of `layer`, inserting it if it is new
+    fn point<R: Rng + ?Sized>(&mut self, layer: &Layer, index: usize, rng: &mut R) -> CriticalPoint {
+        let next_id = self.ids.len();
+        let id = *self.ids.entry((layer.index, index)).or_insert(next_id);
+        let point = CriticalPoint::new(id as i32);
+        if id == next_id {
+            self.reeb.add_node(&point, layer.index as i32, layer.centroids[index].0);
+            if let Some(descriptors) = layer.islands[index].descriptors(rng) {
+                self.reeb.set_descriptors(&point, descriptors);
+            }
+        }
+        point
+    }
+
+    /// Links island `i` of `from` with the islands `matched` of `to`, and appends these to `reached`
+    /// unless they are already present. Returns whether any island was matched.
+    /// `to` may be before or after `from` in time, the edges of the Reeb graph always point forward in time.
+    /// If `gap` is set, the layers in between `from` and `to` were skipped and gap edges are inserted.
+    #[allow(clippy::too_many_arguments)]
+    fn match_island<R: Rng + ?Sized>(
+        &mut self,
+        from: &Layer,
+        i: usize,
+        to: &Layer,
+        matched: &[usize],
+        gap: bool,
+        reached: &mut Vec<usize>,
+        rng: &mut R,
+    ) -> bool {
+        let source = self.point(from, i, rng);
+        for &j in matched {
+            let target = self.point(to, j, rng);
+            let (layer, early, late) = if from.index < to.index {
+                (from.index as i32, &source, &target)
+            } else {
+                (to.index as i32, &target, &source)
+            };
+            if gap {
+                self.reeb.add_gap_edge(layer, early, late);
+            } else {
+                self.reeb.add_edge(layer, early, late);
+            }
+
+            if !reached.contains(&j) {
+                reached.push(j);
+            }
+        }
+        !matched.is_empty()
+    }
+
+    /// Matches the islands of `frontier` with `next_layer`, at `position` in the compared layers, and returns the
+    /// new frontier. Unmatched islands stay in the frontier while they are at most `max_gap` layers away.
+    fn step<R: Rng + ?Sized>(
+        &mut self,
+        frontier: Frontier,
+        next_layer: Rc<Layer>,
+        position: usize,
+        options: &TrackingOptions,
+        started: Instant,
+        rng: &mut R,
+    ) -> Frontier {
+        let mut reached = Vec::new();
+        let mut remaining = Vec::new();
+        for (layer, from, islands) in frontier {
+            let distance = position.abs_diff(from);
+            let matches = options.matching.matches(&layer, &next_layer);
+            let unmatched: Vec<_> = islands
+                .into_iter()
+                .filter(|i| !self.match_island(&layer, *i, &next_layer, &matches[*i], distance > 1, &mut reached, rng))
+                .collect();
+
+            // the unmatched islands may still be found after skipping this layer
+            if !unmatched.is_empty() && distance <= options.max_gap {
+                remaining.push((layer, from, unmatched));
+            }
+        }
+
+        info!(
+            "Layer {}: {} islands, {} tracked, {} queued, {:.2?} elapsed",
+            next_layer.index,
+            next_layer.islands.len(),
+            reached.len(),
+            remaining.iter().map(|(_, _, islands)| islands.len()).sum::<usize>() + reached.len(),
+            started.elapsed()
+        );
+        if !reached.is_empty() {
+            remaining.push((next_layer, position, reached));
+        }
+        remaining
+    }
+}
+
+/// Tracks the islands `starts` (layer, its position in `times` and island index) through the positions `order` of
+/// the layers `times`, either all after or all before the start positions. The islands are swept together, so that
+/// every layer is loaded once for all of them and dropped once no frontier holds it, and the sweep of an island begins
+/// when `order` passes its start position. An island that is not found in the next layer is searched for in up to
+/// `max_gap` further layers, layers that cannot be read are skipped in the same way.
+#[allow(clippy::too_many_arguments)]
+fn sweep<R: Rng + ?Sized, I: Iterator<Item = usize>>(
+    trackers: &mut [Tracker],
+    starts: &[(Rc<Layer>, usize, usize)],
+    order: I,
+    forward: bool,
+    layers: &mut Layers,
+    times: &[usize],
+    options: &TrackingOptions,
+    rng: &mut R,
+) {
+    let started = Instant::now();
+    let mut frontiers: Vec<Frontier> = starts
+        .iter()
+        .map(|(layer, position, index)| vec![(layer.clone(), *position, vec![*index])])
+        .collect();
+    for position in order {
+        if frontiers.iter().all(Vec::is_empty) {
+            break;
+        }
+        let active: Vec<usize> = (0..starts.len())
+            .filter(|&i| !frontiers[i].is_empty())
+            .filter(|&i| {
+                if forward {
+                    starts[i].1 < position
+                } else {
+                    starts[i].1 > position
+                }
+            })
+            .collect();
+        if active.is_empty() {
+            continue;
+        }
+
+        let next = times[position];
+        let next_layer = match layers.get(next, rng) {
+            Ok(layer) => layer,
+            Err(error) => {
+                warn!("Skipping layer {}: {}", next, error);
+                for i in active {
+                    trackers[i]
+                        .skipped
+                        .push((next, std::io::Error::new(error.kind(), error.to_string())));
+                    frontiers[i].retain(|(_, from, _)| position.abs_diff(*from) <= options.max_gap);
+                }
+                continue;
+            }
+        };
+
+        for i in active {
+            let frontier = std::mem::take(&mut frontiers[i]);
+            frontiers[i] = trackers[i].step(frontier, next_layer.clone(), position, options, started, rng);
+        }
+    }
+}
+
+/// Returns the sorted layers (indices into the inputs) that are compared when tracking.
+/// If `layers` is not empty exactly those layers are used, otherwise all layers up to and including
+/// `end_time` (the last layer if `None`) that are a multiple of `stride` layers away from `start_time`.
+pub fn schedule(
+    len: usize,
+    start_time: usize,
+    end_time: Option<usize>,
+    stride: usize,
+    layers: &[usize],
+) -> Result<Vec<usize>, String> {
+    if len == 0 {
+        return Err("No layers found".to_string());
+    }
+    if stride == 0 {
+        return Err("The stride should be at least 1".to_string());
+    }
+    if start_time >= len {
+        return Err(format!(
+            "Start time too large, specify a number between 0 and {}",
+            len - 1
+        ));
+    }
+
+    let mut times: Vec<usize> = if layers.is_empty() {
+        let end_time = end_time.unwrap_or(len - 1);
+        if end_time >= len {
+            return Err(format!(
+                "End time too large, specify a number between 0 and {}",
+                len - 1
+            ));
+        }
+        (start_time % stride..=end_time).step_by(stride).collect()
+    } else {
+        layers.to_vec()
+    };
+
+    times.sort_unstable();
+    times.dedup();
+    if let Some(time) = times.iter().find(|time| **time >= len) {
+        return Err(format!(
+            "Layer {} does not exist, specify layers between 0 and {}",
+            time,
+            len - 1
+        ));
+    }
+    if times.is_empty() {
+        return Err("No layers to compare, the end time is before the start time".to_string());
+    }
+    Ok(times)
+}
+
+/// The island found at the start of tracking
+struct Start {
+    /// Position of the start layer in the compared layers
+    position: usize,
+    layer: Rc<Layer>,
+    index: usize,
+    choice: Choice,
+    /// The layers that could not be read while searching
+    skipped: Vec<(usize, std::io::Error)>,
+}
+
+/// Finds the islands for `region` (see [`Region::choose`]) in the first of the layers `times` at or after `start_time`
+/// (at or before when tracking backward) that has such an island
+fn find_start<R: Rng + ?Sized>(
+    layers: &mut Layers,
+    region: &Region,
+    start_time: usize,
+    times: &[usize],
+    options: &TrackingOptions,
+    rng: &mut R,
+) -> Result<Vec<Start>, TrackingError> {
+    let search: Vec<usize> = match options.direction {
+        Direction::Backward => (0..times.len()).rev().filter(|i| times[*i] <= start_time).collect(),
+        _ => (0..times.len()).filter(|i| times[*i] >= start_time).collect(),
+    };
+
+    if search.is_empty() {
+        return Err(TrackingError::StartTimeOutside { start_time });
+    }
+
+    let mut skipped = Vec::new();
+    for position in search {
+        let layer = match layers.get(times[position], rng) {
+            Ok(layer) => layer,
+            Err(error) => {
+                warn!("Skipping layer {}: {}", times[position], error);
+                skipped.push((times[position], error));
+                continue;
+            }
+        };
+        let chosen = region.choose(&layer.islands, options.tolerance);
+        if !chosen.is_empty() {
+            let starts = chosen
+                .into_iter()
+                .map(|(index, choice)| Start {
+                    position,
+                    layer: layer.clone(),
+                    index,
+                    choice,
+                    skipped: skipped
+                        .iter()
+                        .map(|(layer, error)| (*layer, std::io::Error::new(error.kind(), error.to_string())))
+                        .collect(),
+                })
+                .collect();
+            return Ok(starts);
+        }
+    }
+    Err(TrackingError::NotFound)
+}
+
+/// Tracks the islands `starts` over the layers `times`, the root of each is drawn at its x-coordinate
+fn track<R: Rng + ?Sized>(
+    layers: &mut Layers,
+    starts: Vec<(Start, f64)>,
+    times: &[usize],
+    options: &TrackingOptions,
+    rng: &mut R,
+) -> Vec<Tracked> {
+    let mut trackers = Vec::new();
+    let mut origins = Vec::new();
+    let mut choices = Vec::new();
+    for (start, x) in starts {
+        let Start {
+            position,
+            layer,
+            index,
+            choice,
+            skipped,
+        } = start;
+        info!("Starting at island {} of layer {}: {}", index, layer.index, choice);
+        let root = CriticalPoint::new(0);
+        let mut reeb = ReebGraph::new(&root, x, layer.index as i32);
+        if let Some(descriptors) = layer.islands[index].descriptors(rng) {
+            reeb.set_descriptors(&root, descriptors);
+        }
+
+        let mut ids = FxHashMap::default();
+        ids.insert((layer.index, index), 0);
+        trackers.push(Tracker { reeb, ids, skipped });
+        origins.push((layer, position, index));
+        choices.push(choice);
+    }
+    // the start islands hold their own layers
+    layers.release();
+
+    if options.direction != Direction::Backward {
+        let first = origins
+            .iter()
+            .map(|(_, position, _)| *position)
+            .min()
+            .unwrap_or(times.len());
+        sweep(
+            &mut trackers,
+            &origins,
+            first + 1..times.len(),
+            true,
+            layers,
+            times,
+            options,
+            rng,
+        );
+    }
+    if options.direction != Direction::Forward {
+        let last = origins.iter().map(|(_, position, _)| *position).max().unwrap_or(0);
+        sweep(
+            &mut trackers,
+            &origins,
+            (0..last).rev(),
+            false,
+            layers,
+            times,
+            options,
+            rng,
+        );
+    }
+
+    trackers
+        .into_iter()
+        .zip(origins)
+        .zip(choices)
+        .map(|((tracker, (layer, _, index)), choice)| {
+            let mut islands: Vec<_> = tracker.ids.keys().copied().collect();
+            islands.sort_unstable();
+            Tracked {
+                names: Vec::new(),
+                reeb: tracker.reeb,
+                start_layer: layer.index,
+                start_island: index,
+                islands,
+                choice,
+                skipped: tracker.skipped,
+            }
+        })
+        .collect()
+}
+
+/// Tracks the islands of `region` over the layers `times` (sorted indices into `inputs`), starting at the
+/// first of these layers at or after `start_time` (at or before when tracking backward) and continuing in the direction
+/// of the `options`. The islands are the one containing the point or every island overlapping the polygon, largest
+/// overlap first, or else the nearest islands if they are close enough (see [`Region::choose`]). If there is no such
+/// island, the next layer in that direction with such an island is used instead. Returns a tracked island for every
+/// start island.
+///
+/// Up to `max_gap` layers in which an island is not found, or that cannot be read, are bridged by gap edges.
+/// Consecutive layers in `times` are compared even if they are not adjacent, and the Reeb graph records the
+/// indices into `inputs` as layers.
+pub fn compute_reeb_graph<R: Rng + ?Sized>(
+    inputs: &[Input],
+    region: &Region,
+    start_time: usize,
+    times: &[usize],
+    options: &TrackingOptions,
+    rng: &mut R,
+) -> Result<Vec<Tracked>, TrackingError> {
+    let mut layers = Layers::new(inputs, options, false);
+    let x = region.anchor().0;
+    let starts = find_start(&mut layers, region, start_time, times, options, rng)?;
+    Ok(track(
+        &mut layers,
+        starts.into_iter().map(|start| (start, x)).collect(),
+        times,
+        options,
+        rng,
+    ))
+}
+
+/// Tracks the islands of the `seeds` like [`compute_reeb_graph`], seeds without a start time start at `start_time`.
+/// The islands of all seeds are swept together, so that every layer is loaded once and only the layers that a sweep
+/// can still match stay in memory. Every island that a seed refers to is tracked, and seeds that refer to the same
+/// island at the same start layer are tracked together, the results list the names of these seeds.
+pub fn track_seeds<R: Rng + ?Sized>(
+    inputs: &[Input],
+    seeds: &[Seed],
+    start_time: usize,
+    times: &[usize],
+    options: &TrackingOptions,
+    rng: &mut R,
+) -> TrackedSeeds {
+    let mut layers = Layers::new(inputs, options, seeds.len() > 1);
+
+    // the seeds grouped by their start island, in the order of the seeds
+    let mut starts: Vec<(Vec<&Seed>, Start)> = Vec::new();
+    let mut failed = Vec::new();
+    for seed in seeds {
+        let start_time = seed.start_time.unwrap_or(start_time);
+        let found = match find_start(&mut layers, &seed.region, start_time, times, options, rng) {
+            Ok(found) => found,
+            Err(error) => {
+                failed.push((seed.name.clone(), error));
+                continue;
+            }
+        };
+        for start in found {
+            let same = starts
+                .iter_mut()
+                .find(|(_, other)| other.layer.index == start.layer.index && other.index == start.index);
+            match same {
+                Some((group, _)) => group.push(seed),
+                None => starts.push((vec![seed], start)),
+            }
+        }
+    }
+
+    let (groups, starts): (Vec<_>, Vec<_>) = starts
+        .into_iter()
+        .map(|(group, start)| {
+            let x = group[0].region.anchor().0;
+            (group, (start, x))
+        })
+        .unzip();
+    let mut tracked = track(&mut layers, starts, times, options, rng);
+    for (tracked, group) in tracked.iter_mut().zip(groups) {
+        tracked.names = group.iter().map(|seed| seed.name.clone()).collect();
+    }
+    TrackedSeeds { tracked, failed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reeb_graph::CriticalKind;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn square(x: f64, y: f64, size: f64) -> Polygon {
+        Polygon::new(vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)])
+    }
+
+    /// Islands of `to` matched with the first island of `from` by containment
+    fn containment(from: &Layer, to: &Layer) -> Vec<usize> {
+        Matching::Containment.matches(from, to).swap_remove(0)
+    }
+
+    #[test]
+    fn schedules() {
+        assert_eq!(schedule(10, 0, None, 1, &[]), Ok((0..10).collect()));
+        assert_eq!(schedule(10, 4, Some(8), 3, &[]), Ok(vec![1, 4, 7]));
+        assert_eq!(schedule(10, 4, None, 3, &[9, 2, 2, 5]), Ok(vec![2, 5, 9]));
+        assert!(schedule(10, 0, Some(10), 1, &[]).is_err());
+        assert!(schedule(10, 0, None, 0, &[]).is_err());
+        assert!(schedule(10, 0, None, 1, &[3, 12]).is_err());
+    }
+
+    #[test]
+    fn strided_step_skips_layers() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let early = Layer::new(2, vec![square(0., 0., 4.)], Method::Centroid, &mut rng);
+        let late = Layer::new(7, vec![square(1., 0., 4.)], Method::Centroid, &mut rng);
+
+        let root = CriticalPoint::new(0);
+        let mut ids = FxHashMap::default();
+        ids.insert((2, 0), 0);
+        let mut tracker = Tracker {
+            reeb: ReebGraph::new(&root, 2., 2),
+            ids,
+            skipped: Vec::new(),
+        };
+
+        let mut reached = Vec::new();
+        assert!(tracker.match_island(
+            &early,
+            0,
+            &late,
+            &containment(&early, &late),
+            false,
+            &mut reached,
+            &mut rng
+        ));
+        assert_eq!(reached, vec![0]);
+        let layers: Vec<_> = tracker.reeb.points().map(|(_, layer)| layer).collect();
+        assert!(layers.contains(&7));
+        assert!(!layers.contains(&3));
+    }
+
+    #[test]
+    fn backward_step_is_a_merge() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let early = Layer::new(
+            3,
+            vec![square(0., 0., 2.), square(2., 0., 2.), square(9., 0., 1.)],
+            Method::Centroid,
+            &mut rng,
+        );
+        let late = Layer::new(4, vec![square(0., 0., 4.)], Method::Centroid, &mut rng);
+
+        let root = CriticalPoint::new(0);
+        let mut ids = FxHashMap::default();
+        ids.insert((4, 0), 0);
+        let mut tracker = Tracker {
+            reeb: ReebGraph::new(&root, 2., 4),
+            ids,
+            skipped: Vec::new(),
+        };
+
+        let mut reached = Vec::new();
+        assert!(tracker.match_island(
+            &late,
+            0,
+            &early,
+            &containment(&late, &early),
+            false,
+            &mut reached,
+            &mut rng
+        ));
+        assert_eq!(reached, vec![0, 1]);
+        assert_eq!(tracker.reeb.classify(&root), CriticalKind::Merge);
+        assert_eq!(tracker.reeb.classify(&CriticalPoint::new(1)), CriticalKind::Birth);
+    }
+
+    #[test]
+    fn forward_step_is_a_split() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let early = Layer::new(3, vec![square(0., 0., 4.)], Method::Centroid, &mut rng);
+        let late = Layer::new(
+            4,
+            vec![square(0., 0., 2.), square(2., 0., 2.)],
+            Method::Centroid,
+            &mut rng,
+        );
+
+        let root = CriticalPoint::new(0);
+        let mut ids = FxHashMap::default();
+        ids.insert((3, 0), 0);
+        let mut tracker = Tracker {
+            reeb: ReebGraph::new(&root, 2., 3),
+            ids,
+            skipped: Vec::new(),
+        };
+
+        let mut reached = Vec::new();
+        assert!(tracker.match_island(
+            &early,
+            0,
+            &late,
+            &containment(&early, &late),
+            false,
+            &mut reached,
+            &mut rng
+        ));
+        assert_eq!(reached, vec![0, 1]);
+        assert_eq!(tracker.reeb.classify(&root), CriticalKind::Split);
+        assert_eq!(tracker.reeb.classify(&CriticalPoint::new(2)), CriticalKind::Death);
+    }
+
+    #[test]
+    fn optimal_matching_follows_drift() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let early = Layer::new(
+            3,
+            vec![square(0., 0., 2.), square(3., 0., 2.)],
+            Method::Centroid,
+            &mut rng,
+        );
+        let late = Layer::new(
+            4,
+            vec![square(1.2, 0., 2.), square(4.2, 0., 2.)],
+            Method::Centroid,
+            &mut rng,
+        );
+
+        let optimal = Matching::Optimal {
+            max_cost: 1.5,
+            split_penalty: 0.25,
+        };
+        let parsed = |kind: &str| kind.parse::<MatchingKind>().map(|kind| Matching::new(kind, 1.5, 0.25));
+        assert_eq!(parsed("optimal"), Ok(optimal));
+        assert_eq!(parsed("containment"), Ok(Matching::Containment));
+        assert!("nearest".parse::<MatchingKind>().is_err());
+        assert_eq!(
+            Matching::Containment.matches(&early, &late),
+            vec![Vec::<usize>::new(), Vec::new()]
+        );
+        assert_eq!(optimal.matches(&early, &late), vec![vec![0], vec![1]]);
+    }
+
+    #[test]
+    fn gap_edge() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let early = Layer::new(2, vec![square(0., 0., 4.)], Method::Centroid, &mut rng);
+        let empty = Layer::new(3, vec![square(10., 0., 4.)], Method::Centroid, &mut rng);
+        let late = Layer::new(4, vec![square(1., 0., 4.)], Method::Centroid, &mut rng);
+
+        let root = CriticalPoint::new(0);
+        let mut ids = FxHashMap::default();
+        ids.insert((2, 0), 0);
+        let mut tracker = Tracker {
+            reeb: ReebGraph::new(&root, 2., 2),
+            ids,
+            skipped: Vec::new(),
+        };
+
+        let mut reached = Vec::new();
+        assert!(!tracker.match_island(
+            &early,
+            0,
+            &empty,
+            &containment(&early, &empty),
+            false,
+            &mut reached,
+            &mut rng
+        ));
+        assert!(tracker.match_island(
+            &early,
+            0,
+            &late,
+            &containment(&early, &late),
+            true,
+            &mut reached,
+            &mut rng
+        ));
+        assert!(tracker.reeb.is_gap(&root, &CriticalPoint::new(1)));
+        assert_eq!(tracker.reeb.classify(&root), CriticalKind::Birth);
+    }
+
+    #[test]
+    fn seeds_swept_together() {
+        let dir = std::env::temp_dir().join(format!("island-tracker-sweep-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // two islands side by side, layer 2 cannot be read
+        let network = "id,WKT\n\
+                       1,\"LINESTRING (0 0, 50 0, 100 0, 100 10, 50 10, 0 10, 0 0)\"\n\
+                       2,\"LINESTRING (50 0, 50 10)\"\n";
+        let inputs: Vec<Input> = (0..5).map(|i| Input::File(dir.join(format!("{}.csv", i)))).collect();
+        for i in [0, 1, 3, 4] {
+            std::fs::write(inputs[i].path(), network).unwrap();
+        }
+
+        let seed = |name: &str, x: f64, start_time: usize| Seed {
+            name: name.to_string(),
+            region: Region::Point((x, 5.)),
+            start_time: Some(start_time),
+        };
+        let seeds = [seed("west", 25., 0), seed("east", 75., 3)];
+        let options = TrackingOptions {
+            delta: 0.,
+            direction: Direction::Both,
+            max_gap: 1,
+            ..TrackingOptions::default()
+        };
+        let times: Vec<usize> = (0..5).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+        let together = track_seeds(&inputs, &seeds, 0, &times, &options, &mut rng);
+        assert!(together.failed.is_empty());
+        assert_eq!(together.tracked.len(), 2);
+        for (tracked, seed) in together.tracked.iter().zip(&seeds) {
+            let start_time = seed.start_time.unwrap();
+            let alone = compute_reeb_graph(&inputs, &seed.region, start_time, &times, &options, &mut rng).unwrap();
+            assert_eq!(alone.len(), 1);
+            assert_eq!(tracked.names, std::slice::from_ref(&seed.name));
+            assert_eq!(tracked.islands, alone[0].islands);
+            assert_eq!(
+                tracked.islands.iter().map(|(layer, _)| *layer).collect::<Vec<_>>(),
+                [0, 1, 3, 4]
+            );
+            assert_eq!(tracked.skipped.iter().map(|(layer, _)| *layer).collect::<Vec<_>>(), [2]);
+        }
+        assert_eq!(together.tracked[1].start_layer, 3);
+
+        // a box over both islands, overlapping the east island most
+        let region = Region::bounding_box((40., 0.), (70., 10.));
+        let tracked = compute_reeb_graph(&inputs, &region, 0, &times, &options, &mut rng).unwrap();
+        let starts: Vec<_> = tracked
+            .iter()
+            .map(|tracked| (tracked.start_layer, tracked.start_island))
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                (0, together.tracked[1].start_island),
+                (0, together.tracked[0].start_island)
+            ]
+        );
+        assert!(matches!(tracked[1].choice, Choice::Overlap { area, .. } if area == 100.));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,568 @@
+use std::collections::hash_map::Entry;
+use std::fmt;
+use std::io::{Result, Write};
+
+use fxhash::{FxHashMap, FxHashSet};
+use serde_json::json;
+
+use crate::csv::quote;
+use crate::geometry::{intersections, Segment};
+use crate::graph::UnionFind;
+use crate::io::{fields, parse_i32, Input};
+use crate::wkt::{parse_linestrings, parse_rows};
+
+/// A problem of a network file found by [`validate_ttga`] or [`validate_wkt`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The file cannot be read, or its line `line` cannot be parsed
+    Malformed { line: Option<usize>, message: String },
+    /// The count of vertices or edges at `line` differs from the number of vertices or edges that follow
+    CountMismatch {
+        line: usize,
+        what: &'static str,
+        declared: usize,
+        actual: usize,
+    },
+    /// The path of the edge at `line` has an odd number of coordinates, the last one is ignored
+    OddPath { line: usize },
+    /// The edge at `line` has the weight `nan` (or an empty weight), so it is removed when the network is read
+    NanWeight { line: usize },
+    /// The edge at `line` runs along a segment of the edge at `other`, which may be the same edge
+    DuplicateEdge { line: usize, other: usize },
+    /// The edge at `line` has a segment of length zero, or starts and ends at a vertex without enclosing anything
+    SelfLoop { line: usize },
+    /// The edge at `line` starts or ends at the vertex `vertex`, which is not in the vertex section
+    UnknownVertex { line: usize, vertex: String },
+    /// The edges at `line` and `other`, which may be the same edge, cross or touch at `point` away from a vertex
+    /// they share, so the network is not planar
+    Crossing {
+        line: usize,
+        other: usize,
+        point: (f64, f64),
+    },
+    /// The edges form `components` connected components rather than one
+    Disconnected { components: usize },
+}
+
+impl Problem {
+    /// Returns the line the problem was found at, `None` for problems of the whole file
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Problem::Malformed { line, .. } => *line,
+            Problem::CountMismatch { line, .. }
+            | Problem::OddPath { line }
+            | Problem::NanWeight { line }
+            | Problem::DuplicateEdge { line, .. }
+            | Problem::SelfLoop { line }
+            | Problem::UnknownVertex { line, .. }
+            | Problem::Crossing { line, .. } => Some(*line),
+            Problem::Disconnected { .. } => None,
+        }
+    }
+
+    /// Returns a short name of the kind of problem, e.g. `crossing`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Problem::Malformed { .. } => "malformed",
+            Problem::CountMismatch { .. } => "count",
+            Problem::OddPath { .. } => "odd-path",
+            Problem::NanWeight { .. } => "nan-weight",
+            Problem::DuplicateEdge { .. } => "duplicate",
+            Problem::SelfLoop { .. } => "self-loop",
+            Problem::UnknownVertex { .. } => "unknown-vertex",
+            Problem::Crossing { .. } => "crossing",
+            Problem::Disconnected { .. } => "disconnected",
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Malformed { message, .. } => write!(f, "{}", message),
+            Problem::CountMismatch {
+                what, declared, actual, ..
+            } => write!(f, "{} {} declared, but {} found", declared, what, actual),
+            Problem::OddPath { .. } => write!(f, "the path has an odd number of coordinates"),
+            Problem::NanWeight { .. } => write!(f, "the weight is nan, the edge is removed"),
+            Problem::DuplicateEdge { line, other } if line == other => write!(f, "the edge runs along itself"),
+            Problem::DuplicateEdge { other, .. } => write!(f, "the edge runs along the edge at line {}", other),
+            Problem::SelfLoop { .. } => write!(f, "the edge is a self-loop"),
+            Problem::UnknownVertex { vertex, .. } => write!(f, "unknown vertex {}", vertex),
+            Problem::Crossing { line, other, point } if line == other => {
+                write!(f, "the edge crosses itself at ({}, {})", point.0, point.1)
+            }
+            Problem::Crossing { other, point, .. } => {
+                write!(
+                    f,
+                    "the edge crosses the edge at line {} at ({}, {})",
+                    other, point.0, point.1
+                )
+            }
+            Problem::Disconnected { components } => write!(f, "the edges form {} connected components", components),
+        }
+    }
+}
+
+/// The result of validating a network file
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Number of vertices found in the file
+    pub vertices: usize,
+    /// Number of edges found in the file
+    pub edges: usize,
+    /// The problems in the order of the checks, and of the lines for each check
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    /// Returns the report of a file that cannot be read
+    pub fn unreadable(message: String) -> Report {
+        Report {
+            problems: vec![Problem::Malformed { line: None, message }],
+            ..Report::default()
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// The path of an edge that is kept when the network is read, and the line of the edge
+struct Edge {
+    line: usize,
+    path: Vec<(i32, i32)>,
+}
+
+/// Checks a network in the TTGA format without stopping at the first problem, unlike [`crate::io::parse_ttga`].
+///
+/// The vertex section ends at the first line that does not have three fields, which has to be the edge count, and
+/// every other line is an edge. The edges with a weight other than `nan` are checked for duplicate segments,
+/// crossings and connectivity, see [`validate_edges`].
+pub fn validate_ttga(bytes: &[u8]) -> Report {
+    let mut report = Report::default();
+    let mut lines = bytes
+        .split(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .peekable();
+    let malformed = |line: usize, message: &str| Problem::Malformed {
+        line: Some(line),
+        message: message.to_string(),
+    };
+
+    let count = |line: &[u8]| {
+        std::str::from_utf8(line)
+            .ok()
+            .and_then(|text| text.trim().parse::<usize>().ok())
+    };
+    let declared_vertices = match lines.next().and_then(|(_, line)| count(line)) {
+        Some(declared) => declared,
+        None => {
+            report.problems.push(malformed(1, "expected the vertex count"));
+            return report;
+        }
+    };
+    let mut ids = FxHashSet::default();
+    while let Some((number, line)) = lines.next_if(|(_, line)| fields(line).count() == 3) {
+        let mut fields = fields(line);
+        ids.extend(fields.next());
+        if !fields.all(|coordinate| parse_i32(coordinate).is_some()) {
+            report.problems.push(malformed(number, "malformed vertex"));
+        }
+        report.vertices += 1;
+    }
+    let (edge_count_line, declared_edges) = match lines.next() {
+        Some((number, line)) => match count(line) {
+            Some(declared) => (number, declared),
+            None => {
+                report
+                    .problems
+                    .push(malformed(number, "expected a vertex or the edge count"));
+                return report;
+            }
+        },
+        None => {
+            report
+                .problems
+                .push(malformed(report.vertices + 2, "expected the edge count"));
+            return report;
+        }
+    };
+
+    // blank lines are only allowed at the end of the file
+    let mut lines: Vec<_> = lines.collect();
+    while lines.last().is_some_and(|(_, line)| fields(line).next().is_none()) {
+        lines.pop();
+    }
+    let mut edges = Vec::new();
+    for (number, line) in lines {
+        report.edges += 1;
+        let mut fields = fields(line);
+        let (from, to, weight) = match (fields.next(), fields.next(), fields.next(), fields.next()) {
+            (Some(_), Some(from), Some(to), Some(weight)) => (from, to, weight),
+            _ => {
+                report
+                    .problems
+                    .push(malformed(number, "expected an id, two vertices and a weight"));
+                continue;
+            }
+        };
+        for vertex in [from, to] {
+            if !ids.contains(vertex) {
+                report.problems.push(Problem::UnknownVertex {
+                    line: number,
+                    vertex: String::from_utf8_lossy(vertex).into_owned(),
+                });
+            }
+        }
+        let weight = match std::str::from_utf8(weight)
+            .ok()
+            .and_then(|weight| weight.parse::<f64>().ok())
+        {
+            Some(weight) => weight,
+            None => {
+                report.problems.push(malformed(number, "malformed edge weight"));
+                continue;
+            }
+        };
+        if weight.is_nan() {
+            report.problems.push(Problem::NanWeight { line: number });
+        }
+
+        let coordinates: Option<Vec<_>> = fields.map(parse_i32).collect();
+        let coordinates = match coordinates {
+            Some(coordinates) => coordinates,
+            None => {
+                report.problems.push(malformed(number, "malformed edge path"));
+                continue;
+            }
+        };
+        if coordinates.len() % 2 == 1 {
+            report.problems.push(Problem::OddPath { line: number });
+        }
+        let path: Vec<_> = coordinates.chunks_exact(2).map(|point| (point[0], point[1])).collect();
+        let closed = from == to && path.len() < 4;
+        if closed || path.windows(2).any(|segment| segment[0] == segment[1]) {
+            report.problems.push(Problem::SelfLoop { line: number });
+        }
+        if !weight.is_nan() {
+            edges.push(Edge { line: number, path });
+        }
+    }
+
+    if report.vertices != declared_vertices {
+        report.problems.insert(
+            0,
+            Problem::CountMismatch {
+                line: 1,
+                what: "vertices",
+                declared: declared_vertices,
+                actual: report.vertices,
+            },
+        );
+    }
+    if report.edges != declared_edges {
+        let position = report
+            .problems
+            .iter()
+            .take_while(|problem| problem.line() < Some(edge_count_line))
+            .count();
+        report.problems.insert(
+            position,
+            Problem::CountMismatch {
+                line: edge_count_line,
+                what: "edges",
+                declared: declared_edges,
+                actual: report.edges,
+            },
+        );
+    }
+    validate_edges(&edges, &mut report.problems);
+    report
+}
+
+/// Checks a network of WKT line strings, see [`crate::wkt::parse_network`]. The coordinates are rounded like when
+/// the network is read, and the channels with a weight are checked like the edges of a TTGA network, see
+/// [`validate_edges`].
+pub fn validate_wkt(bytes: &[u8]) -> Report {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => return Report::unreadable(e.to_string()),
+    };
+    let rows = match parse_rows(text) {
+        Ok(rows) => rows,
+        Err(e) => return Report::unreadable(e.to_string()),
+    };
+    let mut report = Report::default();
+    let mut edges = Vec::new();
+    for row in rows {
+        report.edges += 1;
+        let malformed = |e: std::io::Error| Problem::Malformed {
+            line: Some(row.line),
+            message: e.to_string(),
+        };
+        let weight = match row.weight() {
+            Ok(weight) => weight,
+            Err(e) => {
+                report.problems.push(malformed(e));
+                continue;
+            }
+        };
+        if weight.is_some_and(f64::is_nan) {
+            report.problems.push(Problem::NanWeight { line: row.line });
+            continue;
+        }
+        match parse_linestrings(&row.geometry) {
+            Ok(linestrings) => edges.extend(linestrings.into_iter().map(|linestring| {
+                let mut path: Vec<_> = linestring
+                    .into_iter()
+                    .map(|(x, y)| (x.round() as i32, y.round() as i32))
+                    .collect();
+                // segments shorter than the rounding are skipped when the network is read
+                path.dedup();
+                Edge { line: row.line, path }
+            })),
+            Err(e) => report.problems.push(malformed(e)),
+        }
+    }
+    report.vertices = edges
+        .iter()
+        .flat_map(|edge| edge.path.iter())
+        .collect::<FxHashSet<_>>()
+        .len();
+    validate_edges(&edges, &mut report.problems);
+    report
+}
+
+/// Checks that no two segments of the `edges` coincide or cross, and that the edges are connected. Every pair of
+/// edges is reported once for each kind of problem.
+fn validate_edges(edges: &[Edge], problems: &mut Vec<Problem>) {
+    // the segments with their ends ordered, and the line of the first edge along each of them
+    let mut segments: FxHashMap<Segment, usize> = FxHashMap::default();
+    let mut duplicates = FxHashSet::default();
+    for edge in edges {
+        for segment in edge.path.windows(2).filter(|segment| segment[0] != segment[1]) {
+            let key = (segment[0].min(segment[1]), segment[0].max(segment[1]));
+            match segments.entry(key) {
+                Entry::Occupied(other) => {
+                    if duplicates.insert((*other.get(), edge.line)) {
+                        problems.push(Problem::DuplicateEdge {
+                            line: edge.line,
+                            other: *other.get(),
+                        });
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(edge.line);
+                }
+            }
+        }
+    }
+
+    // the distinct segments, sorted so the problems do not depend on the order of the hash map
+    let mut sorted: Vec<_> = segments.iter().map(|(&segment, &line)| (segment, line)).collect();
+    sorted.sort_unstable();
+    let distinct: Vec<Segment> = sorted.iter().map(|&(segment, _)| segment).collect();
+    let mut crossings = FxHashSet::default();
+    for intersection in intersections(&distinct) {
+        for &i in &intersection.inside {
+            for &j in intersection.inside.iter().chain(&intersection.ends) {
+                let (line, other) = (sorted[i].1, sorted[j].1);
+                let pair = (line.min(other), line.max(other));
+                if i != j && crossings.insert(pair) {
+                    problems.push(Problem::Crossing {
+                        line: pair.1,
+                        other: pair.0,
+                        point: intersection.point,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut points = FxHashMap::default();
+    let mut sets = UnionFind::new(0);
+    let mut index = |point: (i32, i32), sets: &mut UnionFind| *points.entry(point).or_insert_with(|| sets.push());
+    for &(a, b) in segments.keys() {
+        let (a, b) = (index(a, &mut sets), index(b, &mut sets));
+        sets.union(a, b);
+    }
+    let components = sets.sets();
+    if components > 1 {
+        problems.push(Problem::Disconnected { components });
+    }
+}
+
+/// Writes the `reports` of the layers as text, a line per file followed by its problems
+pub fn write_text<W: Write>(reports: &[(usize, &Input, Report)], out: &mut W) -> Result<()> {
+    for (layer, input, report) in reports {
+        let status = match report.problems.len() {
+            0 => "ok".to_string(),
+            1 => "1 problem".to_string(),
+            problems => format!("{} problems", problems),
+        };
+        writeln!(
+            out,
+            "{} (layer {}): {}, {} vertices and {} edges",
+            input, layer, status, report.vertices, report.edges
+        )?;
+        for problem in &report.problems {
+            match problem.line() {
+                Some(line) => writeln!(out, "  line {}: {}", line, problem)?,
+                None => writeln!(out, "  {}", problem)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the `reports` of the layers as CSV, a row per problem and a row without a problem for a valid file
+pub fn write_csv<W: Write>(reports: &[(usize, &Input, Report)], out: &mut W) -> Result<()> {
+    writeln!(out, "layer,file,vertices,edges,line,problem,message")?;
+    for (layer, input, report) in reports {
+        let file = quote(&input.to_string());
+        let prefix = format!("{},{},{},{}", layer, file, report.vertices, report.edges);
+        if report.is_valid() {
+            writeln!(out, "{},,,", prefix)?;
+        }
+        for problem in &report.problems {
+            let line = problem.line().map(|line| line.to_string()).unwrap_or_default();
+            let message = quote(&problem.to_string());
+            writeln!(out, "{},{},{},{}", prefix, line, problem.kind(), message)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the `reports` of the layers as a JSON array with an object per file
+pub fn write_json<W: Write>(reports: &[(usize, &Input, Report)], out: &mut W) -> Result<()> {
+    let reports: Vec<_> = reports
+        .iter()
+        .map(|(layer, input, report)| {
+            let problems: Vec<_> = report
+                .problems
+                .iter()
+                .map(|problem| {
+                    json!({"line": problem.line(), "problem": problem.kind(), "message": problem.to_string()})
+                })
+                .collect();
+            json!({
+                "layer": layer,
+                "file": input.to_string(),
+                "vertices": report.vertices,
+                "edges": report.edges,
+                "problems": problems,
+            })
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &reports)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(report: &Report) -> Vec<(Option<usize>, &'static str)> {
+        report
+            .problems
+            .iter()
+            .map(|problem| (problem.line(), problem.kind()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_network() {
+        let text = "4\n0 0 0\n1 10 0\n2 10 10\n3 0 10\n\
+                    3\n0 0 1 200 0 0 10 0\n1 1 3 150 10 0 10 10 0 10\n2 3 0 nan 0 10 0 0\n\n";
+        let report = validate_ttga(text.as_bytes());
+        assert_eq!((report.vertices, report.edges), (4, 3));
+        assert_eq!(kinds(&report), vec![(Some(9), "nan-weight")]);
+    }
+
+    #[test]
+    fn problems() {
+        let text = "4\n0 0 0\n1 10 0\n2 10 10\n\
+                    7\n\
+                    0 0 1 200 0 0 10 0\n\
+                    1 1 2 200 10 0 10 10 0 10 0\n\
+                    2 0 7 200 0 0 10 10\n\
+                    3 1 0 200 10 0 0 0\n\
+                    4 2 2 200 10 10 10 10\n\
+                    5 0 2 200 0 5 20 5\n\
+                    6 0 1 200 50 50 60 50\n";
+        let report = validate_ttga(text.as_bytes());
+        assert_eq!(
+            kinds(&report),
+            vec![
+                (Some(1), "count"),
+                (Some(7), "odd-path"),
+                (Some(8), "unknown-vertex"),
+                (Some(10), "self-loop"),
+                (Some(9), "duplicate"),
+                (Some(11), "crossing"),
+                (Some(11), "crossing"),
+                (None, "disconnected"),
+            ]
+        );
+        assert_eq!(report.problems[4].to_string(), "the edge runs along the edge at line 6");
+        assert_eq!(
+            report.problems[5].to_string(),
+            "the edge crosses the edge at line 8 at (5, 5)"
+        );
+        assert_eq!(report.problems[7], Problem::Disconnected { components: 3 });
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(kinds(&validate_ttga(b"x\n")), vec![(Some(1), "malformed")]);
+        let report = validate_ttga(b"2\n0 0 0\n1 0\n1\n0 0 1 1 0 0 1 0\n");
+        assert_eq!(kinds(&report), vec![(Some(3), "malformed")]);
+        let report = validate_ttga(b"2\n0 0 0\n1 1 0\n3\n0 0 1 1 0 0 1 0\n1 0 1 x 0 0\n");
+        assert_eq!(kinds(&report), vec![(Some(4), "count"), (Some(6), "malformed")]);
+    }
+
+    #[test]
+    fn wkt() {
+        let text = "id,wkt,weight\n\
+                    0,\"LINESTRING (0 0, 10 0.2, 10 10)\",200\n\
+                    1,\"LINESTRING (0 10, 10 0)\",150\n\
+                    2,\"LINESTRING (0 0, 5 5)\",\n\
+                    3,\"LINESTRING (0 0, 10 10)\",1\n";
+        let report = validate_wkt(text.as_bytes());
+        assert_eq!((report.vertices, report.edges), (4, 4));
+        assert_eq!(kinds(&report), vec![(Some(4), "nan-weight"), (Some(5), "crossing")]);
+    }
+
+    #[test]
+    fn written_reports() {
+        let (valid, invalid) = (Input::File("a.txt".into()), Input::File("b,c.txt".into()));
+        let reports = [
+            (0, &valid, Report::default()),
+            (1, &invalid, validate_ttga(b"2\n0 0 0\n1 0\n1\n0 0 1 1 0 0 1 0\n")),
+        ];
+        let mut out = Vec::new();
+        write_csv(&reports, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert_eq!(csv.lines().nth(1), Some("0,a.txt,0,0,,,"));
+        assert!(csv
+            .lines()
+            .nth(2)
+            .unwrap()
+            .starts_with("1,\"b,c.txt\",1,0,3,malformed,"));
+
+        let mut out = Vec::new();
+        write_json(&reports, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[1]["problems"][0]["line"], json!(3));
+
+        let mut out = Vec::new();
+        write_text(&reports, &mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("a.txt (layer 0): ok, 0 vertices and 0 edges\n"));
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use fxhash::FxHashMap;
+
+use crate::csv::split_csv;
+use crate::graph::Graph;
+use crate::io::read_file;
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Reads a network from a CSV file, possibly compressed, with one channel per row, see [`parse_network`]
+pub fn read_wkt_network(delta: f64, path: &Path) -> Result<Graph> {
+    let text = String::from_utf8(read_file(path)?).map_err(|e| invalid_data(e.to_string()))?;
+    parse_network(delta, &text)
+}
+
+/// Parses a network from CSV with a header containing the column `wkt` (or `geometry`), holding the channel as a
+/// WKT `LINESTRING` or `MULTILINESTRING`, and optionally the column `weight`. As for TTGA networks, the channels with
+/// a weight of at least `delta` are kept, channels with an empty or `nan` weight are removed. Without a weight
+/// column all the channels are kept, with an unknown weight.
+///
+/// The WKT needs to be quoted unless it is the only field containing commas. The vertices of the graph have integer
+/// coordinates, so the coordinates are rounded, and line strings ending at the same rounded point are joined there.
+pub fn parse_network(delta: f64, text: &str) -> Result<Graph> {
+    let mut graph = Graph::new();
+    let mut vertices = FxHashMap::default();
+    let mut vertex = |graph: &mut Graph, (x, y): (f64, f64)| {
+        let point = (x.round() as i32, y.round() as i32);
+        *vertices.entry(point).or_insert_with(|| {
+            graph.add_vertex(point);
+            graph.vertices() - 1
+        })
+    };
+    for row in parse_rows(text)? {
+        let weight = match row.weight() {
+            Ok(Some(weight)) if weight.is_nan() || weight < delta => continue,
+            Ok(weight) => weight,
+            Err(e) => return Err(invalid_data(format!("line {}: {}", row.line, e))),
+        };
+        let linestrings =
+            parse_linestrings(&row.geometry).map_err(|e| invalid_data(format!("line {}: {}", row.line, e)))?;
+        for linestring in linestrings {
+            for segment in linestring.windows(2) {
+                let from = vertex(&mut graph, segment[0]);
+                let to = vertex(&mut graph, segment[1]);
+                // segments shorter than the rounding collapse to a point
+                if from != to {
+                    graph.add_weighted_edge(from, to, weight.unwrap_or(f64::NAN));
+                }
+            }
+        }
+    }
+    Ok(graph)
+}
+
+/// A channel of a WKT network, see [`parse_network`]
+pub(crate) struct Row {
+    /// Line of the channel in the file, counted from 1
+    pub line: usize,
+    pub geometry: String,
+    /// The weight field, `None` without a weight column
+    pub weight: Option<String>,
+}
+
+impl Row {
+    /// Returns the weight of the channel, `NAN` if the channel is removed for its empty or `nan` weight, and `None`
+    /// without a weight column
+    pub fn weight(&self) -> Result<Option<f64>> {
+        match self.weight.as_deref() {
+            None => Ok(None),
+            Some("") | Some("nan") => Ok(Some(f64::NAN)),
+            Some(value) => value
+                .parse::<f64>()
+                .map(Some)
+                .map_err(|e| invalid_data(format!("invalid weight {:?}: {}", value, e))),
+        }
+    }
+}
+
+fn column(header: &[String], name: &str) -> Option<usize> {
+    header.iter().position(|column| column.eq_ignore_ascii_case(name))
+}
+
+/// Returns the column of the channels in the `header` of a WKT network, `wkt` or else `geometry`
+fn geometry_column(header: &[String]) -> Option<usize> {
+    column(header, "wkt").or_else(|| column(header, "geometry"))
+}
+
+/// Returns whether the first line of a CSV file is the header of a WKT network, see [`parse_network`]
+pub(crate) fn is_network_header(line: &str) -> bool {
+    geometry_column(&split_csv(line)).is_some()
+}
+
+/// Splits a WKT network into its channels, see [`parse_network`]
+pub(crate) fn parse_rows(text: &str) -> Result<Vec<Row>> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header = match lines.next() {
+        Some((_, header)) => split_csv(header),
+        None => return Ok(Vec::new()),
+    };
+    let geometry = geometry_column(&header)
+        .ok_or_else(|| invalid_data("the network should have the column wkt or geometry".to_string()))?;
+    let weight = column(&header, "weight");
+
+    Ok(lines
+        .map(|(line, text)| {
+            let mut fields = split_csv(text);
+            // the commas of an unquoted WKT split it into several fields
+            if fields.len() > header.len() {
+                let extra = fields.len() - header.len();
+                let joined = fields.drain(geometry..=geometry + extra).collect::<Vec<_>>().join(",");
+                fields.insert(geometry, joined);
+            }
+            let field = |index: usize| fields.get(index).cloned().unwrap_or_default();
+            Row {
+                line,
+                geometry: field(geometry),
+                weight: weight.map(field),
+            }
+        })
+        .collect())
+}
+
+/// Parses the line strings of a WKT `LINESTRING` or `MULTILINESTRING`, the coordinates beyond x and y are ignored
+pub fn parse_linestrings(text: &str) -> Result<Vec<Vec<(f64, f64)>>> {
+    let text = text.trim();
+    let start = text.find('(').unwrap_or(text.len());
+    let tag = text[..start].to_ascii_uppercase();
+    let mut words = tag.split_whitespace();
+    let kind = words.next().unwrap_or("");
+    if words.any(|word| word == "EMPTY") {
+        return Ok(Vec::new());
+    }
+    let body = text[start..]
+        .strip_prefix('(')
+        .and_then(|body| body.strip_suffix(')'))
+        .ok_or_else(|| invalid_data(format!("malformed WKT {:?}", text)))?;
+    match kind {
+        "LINESTRING" => Ok(vec![parse_points(body)?]),
+        "MULTILINESTRING" => body
+            .split(')')
+            .map(|part| part.trim().trim_start_matches(',').trim())
+            .filter(|part| !part.is_empty())
+            .map(|part| match part.strip_prefix('(') {
+                Some(points) => parse_points(points),
+                None => Err(invalid_data(format!("malformed WKT {:?}", text))),
+            })
+            .collect(),
+        _ => Err(invalid_data(format!(
+            "expected a LINESTRING or MULTILINESTRING, got {:?}",
+            kind
+        ))),
+    }
+}
+
+/// Parses comma separated points of whitespace separated coordinates
+fn parse_points(text: &str) -> Result<Vec<(f64, f64)>> {
+    text.split(',')
+        .map(|point| {
+            let coordinates = point
+                .split_whitespace()
+                .map(|coordinate| coordinate.parse::<f64>())
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| invalid_data(format!("invalid point {:?}: {}", point.trim(), e)))?;
+            match coordinates[..] {
+                [x, y, ..] => Ok((x, y)),
+                _ => Err(invalid_data(format!("invalid point {:?}", point.trim()))),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linestrings() {
+        assert_eq!(
+            parse_linestrings("LINESTRING (0 0, 10.5 0, 10 10)").unwrap(),
+            vec![vec![(0., 0.), (10.5, 0.), (10., 10.)]]
+        );
+        assert_eq!(
+            parse_linestrings("MultiLineString Z((0 0 1, 1 0 1), (2 2 0,3 3 0))").unwrap(),
+            vec![vec![(0., 0.), (1., 0.)], vec![(2., 2.), (3., 3.)]]
+        );
+        assert!(parse_linestrings("LINESTRING EMPTY").unwrap().is_empty());
+        assert!(parse_linestrings("POINT (1 2)").is_err());
+        assert!(parse_linestrings("LINESTRING (0 0, 1)").is_err());
+    }
+
+    #[test]
+    fn network() {
+        let text = "id,WKT,weight\n\
+                    0,\"LINESTRING (0 0, 10 0, 10 10)\",200\n\
+                    1,\"LINESTRING (10 10, 0 10, 0.2 -0.3)\",150\n\
+                    2,\"LINESTRING (0 0, 10 10)\",50\n\
+                    3,\"LINESTRING (0 0, 5 5)\",nan\n";
+        let graph = parse_network(100., text).unwrap();
+        assert_eq!(graph.vertices(), 4);
+        assert_eq!(graph.edges(), 4);
+        let islands = graph.polygons();
+        assert_eq!(islands.len(), 1);
+        assert_eq!(islands[0].area(), 100.);
+        assert_eq!(islands[0].min_boundary_weight(), Some(150.));
+
+        let unweighted = parse_network(1e9, "id,geometry\n0,LINESTRING (0 0, 10 0, 10 10, 0 0)\n").unwrap();
+        assert_eq!(unweighted.polygons().len(), 1);
+        assert!(parse_network(0., "weight\n1\n").is_err());
+    }
+}